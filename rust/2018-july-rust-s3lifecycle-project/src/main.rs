@@ -101,6 +101,38 @@ fn main() {
                         .help("Days until expiration (deletion)")
                         .takes_value(true),
                 )
+                .arg(
+                    Arg::with_name("ia-date")
+                        .long("ia-date")
+                        .value_name("DATE")
+                        .help("Date of transition to STANDARD_IA (YYYY-MM-DD)")
+                        .conflicts_with("ia-days")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("glacier-date")
+                        .long("glacier-date")
+                        .value_name("DATE")
+                        .help("Date of transition to GLACIER (YYYY-MM-DD)")
+                        .conflicts_with("glacier-days")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("deep-archive-date")
+                        .long("deep-archive-date")
+                        .value_name("DATE")
+                        .help("Date of transition to DEEP_ARCHIVE (YYYY-MM-DD)")
+                        .conflicts_with("deep-archive-days")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("expiration-date")
+                        .long("expiration-date")
+                        .value_name("DATE")
+                        .help("Date of expiration (deletion) (YYYY-MM-DD)")
+                        .conflicts_with("expiration-days")
+                        .takes_value(true),
+                )
                 .arg(
                     Arg::with_name("enabled")
                         .long("enabled")
@@ -207,6 +239,10 @@ fn run(matches: ArgMatches) -> Result<(), String> {
             let glacier_days = sub_m.value_of("glacier-days").and_then(|d| d.parse::<i64>().ok());
             let deep_archive_days = sub_m.value_of("deep-archive-days").and_then(|d| d.parse::<i64>().ok());
             let expiration_days = sub_m.value_of("expiration-days").and_then(|d| d.parse::<i64>().ok());
+            let ia_date = sub_m.value_of("ia-date").map(lifecycle_date).transpose()?;
+            let glacier_date = sub_m.value_of("glacier-date").map(lifecycle_date).transpose()?;
+            let deep_archive_date = sub_m.value_of("deep-archive-date").map(lifecycle_date).transpose()?;
+            let expiration_date = sub_m.value_of("expiration-date").map(lifecycle_date).transpose()?;
             let enabled = sub_m.is_present("enabled");
             
             create_lifecycle_rule(
@@ -215,10 +251,10 @@ fn run(matches: ArgMatches) -> Result<(), String> {
                 bucket,
                 id,
                 prefix,
-                ia_days,
-                glacier_days,
-                deep_archive_days,
-                expiration_days,
+                (ia_days, ia_date),
+                (glacier_days, glacier_date),
+                (deep_archive_days, deep_archive_date),
+                (expiration_days, expiration_date),
                 enabled,
             )?;
         }
@@ -277,16 +313,17 @@ fn list_lifecycle_rules(
     Ok(())
 }
 
+// Each lifecycle action is given as (days, date); at most one of the two is set.
 fn create_lifecycle_rule(
     core: &mut Core,
     client: &S3Client,
     bucket: &str,
     id: &str,
     prefix: Option<&str>,
-    ia_days: Option<i64>,
-    glacier_days: Option<i64>,
-    deep_archive_days: Option<i64>,
-    expiration_days: Option<i64>,
+    ia: (Option<i64>, Option<String>),
+    glacier: (Option<i64>, Option<String>),
+    deep_archive: (Option<i64>, Option<String>),
+    expiration: (Option<i64>, Option<String>),
     enabled: bool,
 ) -> Result<(), String> {
     // Get existing rules
@@ -302,27 +339,18 @@ fn create_lifecycle_rule(
     // Build transitions
     let mut transitions = Vec::new();
 
-    if let Some(days) = ia_days {
-        transitions.push(Transition {
-            days: Some(days),
-            date: None,
-            storage_class: Some("STANDARD_IA".to_string()),
-        });
-    }
-
-    if let Some(days) = glacier_days {
-        transitions.push(Transition {
-            days: Some(days),
-            date: None,
-            storage_class: Some("GLACIER".to_string()),
-        });
-    }
-
-    if let Some(days) = deep_archive_days {
+    for (action, storage_class) in vec![
+        (ia, "STANDARD_IA"),
+        (glacier, "GLACIER"),
+        (deep_archive, "DEEP_ARCHIVE"),
+    ] {
+        if let (None, None) = action {
+            continue;
+        }
         transitions.push(Transition {
-            days: Some(days),
-            date: None,
-            storage_class: Some("DEEP_ARCHIVE".to_string()),
+            days: action.0,
+            date: action.1,
+            storage_class: Some(storage_class.to_string()),
         });
     }
 
@@ -333,11 +361,14 @@ fn create_lifecycle_rule(
     };
 
     // Build expiration
-    let expiration = expiration_days.map(|days| LifecycleExpiration {
-        days: Some(days),
-        date: None,
-        expired_object_delete_marker: None,
-    });
+    let expiration = match expiration {
+        (None, None) => None,
+        (days, date) => Some(LifecycleExpiration {
+            days,
+            date,
+            expired_object_delete_marker: None,
+        }),
+    };
 
     // Build rule
     let new_rule = LifecycleRule {
@@ -502,6 +533,40 @@ fn archive_objects(
     Ok(())
 }
 
+// S3 only accepts lifecycle dates at midnight UTC, in ISO 8601 form
+fn lifecycle_date(date: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid date '{}' (expected YYYY-MM-DD)", date);
+
+    let parts: Vec<&str> = date.split('-').collect();
+    let well_formed = parts.len() == 3
+        && parts[0].len() == 4
+        && parts[1].len() == 2
+        && parts[2].len() == 2
+        && parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()));
+
+    if !well_formed {
+        return Err(invalid());
+    }
+
+    let year: u32 = parts[0].parse().map_err(|_| invalid())?;
+    let month: u32 = parts[1].parse().map_err(|_| invalid())?;
+    let day: u32 = parts[2].parse().map_err(|_| invalid())?;
+    if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+
+    Ok(format!("{}T00:00:00.000Z", date))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
 fn print_rule(rule: &LifecycleRule) {
     println!("\nRule ID: {}", rule.id.as_ref().unwrap_or(&"N/A".to_string()));
     println!("Status: {}", rule.status);
@@ -523,6 +588,12 @@ fn print_rule(rule: &LifecycleRule) {
                     days,
                     t.storage_class.as_ref().unwrap_or(&"N/A".to_string())
                 );
+            } else if let Some(ref date) = t.date {
+                println!(
+                    "  - On {} → {}",
+                    date.get(..10).unwrap_or(date),
+                    t.storage_class.as_ref().unwrap_or(&"N/A".to_string())
+                );
             }
         }
     }
@@ -530,6 +601,8 @@ fn print_rule(rule: &LifecycleRule) {
     if let Some(ref expiration) = rule.expiration {
         if let Some(days) = expiration.days {
             println!("\nExpiration: {} days", days);
+        } else if let Some(ref date) = expiration.date {
+            println!("\nExpiration: on {}", date.get(..10).unwrap_or(date));
         }
     }
 
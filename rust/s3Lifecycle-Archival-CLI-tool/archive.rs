@@ -0,0 +1,1818 @@
+// Immediate archival: copies objects onto themselves with a colder storage class.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Context, Result};
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::{
+    AccessControlPolicy, ChecksumAlgorithm, ChecksumMode, CompletedMultipartUpload, CompletedPart,
+    Grant, MetadataDirective, Object, ObjectStorageClass, Owner, Permission, ServerSideEncryption,
+    StorageClass, TaggingDirective,
+};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::cost::RegionPricing;
+use crate::journal::{Entry, Journal, ResumeState};
+use crate::output::OutputFormat;
+use crate::retry::Retry;
+use crate::validate::storage_class_rank;
+
+/// Largest source `copy_object` accepts; bigger objects are copied in parts.
+pub const MAX_COPY_OBJECT_SIZE: i64 = 5 << 30;
+pub const MIN_PART_SIZE: i64 = 5 << 20;
+pub const MAX_PART_SIZE: i64 = 5 << 30;
+pub const DEFAULT_PART_SIZE: i64 = 512 << 20;
+const MAX_PARTS: i64 = 10_000;
+
+pub struct ArchiveOptions {
+    /// Number of objects copied in parallel
+    pub concurrency: usize,
+    /// Recorded in the journal, so a resumed run picks the same objects and copies them the
+    /// same way
+    pub settings: ArchiveSettings,
+    /// Compiled from `settings`
+    pub filter: ObjectFilter,
+    /// Checkpoint journal to write, or to continue when resuming
+    pub journal: PathBuf,
+    pub resume: Option<ResumeState>,
+    /// Where to write failures with `continue_on_error`; `.csv` files get CSV, others JSON
+    pub failure_report: PathBuf,
+    /// List what would be archived without copying anything
+    pub dry_run: bool,
+    /// Prices for the dry-run cost estimate
+    pub pricing: Option<RegionPricing>,
+    pub retry: Retry,
+    /// JSON or YAML replaces the progress messages with a summary at the end
+    pub output: Option<OutputFormat>,
+}
+
+impl ArchiveOptions {
+    fn structured_output(&self) -> Option<OutputFormat> {
+        self.output.filter(|format| format.is_structured())
+    }
+}
+
+/// Which objects a run archives and how they're copied, as given on the command line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveSettings {
+    pub older_than: Option<i64>,
+    pub newer_than: Option<i64>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    /// Glob patterns, compiled with `key_glob`
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub regex: Option<String>,
+    pub tags: Vec<(String, String)>,
+    pub preserve: PreserveOptions,
+    /// Part size for multipart copies of objects over `MAX_COPY_OBJECT_SIZE`
+    pub part_size: i64,
+    /// Record failed objects and carry on, instead of stopping at the first failure
+    pub continue_on_error: bool,
+}
+
+impl ArchiveSettings {
+    pub fn filter(&self) -> Result<ObjectFilter> {
+        let glob_set = |patterns: &[String]| -> Result<Option<GlobSet>> {
+            if patterns.is_empty() {
+                return Ok(None);
+            }
+            let mut builder = GlobSetBuilder::new();
+            for pattern in patterns {
+                builder.add(
+                    key_glob(pattern)
+                        .with_context(|| format!("Invalid glob pattern: {}", pattern))?,
+                );
+            }
+            Ok(Some(builder.build().context("Invalid glob pattern")?))
+        };
+
+        Ok(ObjectFilter {
+            older_than: self.older_than,
+            newer_than: self.newer_than,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            include: glob_set(&self.include)?,
+            exclude: glob_set(&self.exclude)?,
+            regex: self
+                .regex
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .context("Invalid regular expression")?,
+            tags: self.tags.clone(),
+        })
+    }
+
+    /// Flag of the first option set in `self` that differs from `recorded`, the settings of
+    /// the run being resumed. Options left at their defaults count as not set.
+    pub fn conflicting_flag(&self, recorded: &ArchiveSettings) -> Option<&'static str> {
+        fn differs<T: Default + PartialEq>(given: &T, recorded: &T) -> bool {
+            *given != T::default() && given != recorded
+        }
+
+        let (given, preserve) = (&self.preserve, &recorded.preserve);
+        [
+            (
+                "older-than",
+                differs(&self.older_than, &recorded.older_than),
+            ),
+            (
+                "newer-than",
+                differs(&self.newer_than, &recorded.newer_than),
+            ),
+            ("min-size", differs(&self.min_size, &recorded.min_size)),
+            ("max-size", differs(&self.max_size, &recorded.max_size)),
+            ("include", differs(&self.include, &recorded.include)),
+            ("exclude", differs(&self.exclude, &recorded.exclude)),
+            ("regex", differs(&self.regex, &recorded.regex)),
+            ("tag", differs(&self.tags, &recorded.tags)),
+            ("preserve-sse", differs(&given.sse, &preserve.sse)),
+            (
+                "sse-kms-key-id",
+                differs(&given.sse_kms_key_id, &preserve.sse_kms_key_id),
+            ),
+            ("preserve-tags", differs(&given.tags, &preserve.tags)),
+            ("preserve-acl", differs(&given.acl, &preserve.acl)),
+            (
+                "preserve-checksum",
+                differs(&given.checksum, &preserve.checksum),
+            ),
+            ("part-size", differs(&self.part_size, &recorded.part_size)),
+            (
+                "continue-on-error",
+                differs(&self.continue_on_error, &recorded.continue_on_error),
+            ),
+        ]
+        .into_iter()
+        .find_map(|(flag, differs)| differs.then_some(flag))
+    }
+}
+
+/// Compiles a key pattern: `*` stops at `/`, `**` does not.
+pub fn key_glob(pattern: &str) -> Result<Glob, globset::Error> {
+    GlobBuilder::new(pattern).literal_separator(true).build()
+}
+
+/// Selects which listed objects get archived. Every filter that is set must match.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectFilter {
+    /// Minimum age in seconds
+    pub older_than: Option<i64>,
+    /// Maximum age in seconds
+    pub newer_than: Option<i64>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    /// Key must match one of these globs, if any are given
+    pub include: Option<GlobSet>,
+    pub exclude: Option<GlobSet>,
+    pub regex: Option<Regex>,
+    /// Checked with `get_object_tagging` for each object that passes the listing filters
+    pub tags: Vec<(String, String)>,
+}
+
+impl ObjectFilter {
+    /// Checks everything the listing tells us about an object; tags need a separate request.
+    pub fn matches_listing(&self, object: &Object, now: i64) -> bool {
+        let key = object.key.as_deref().unwrap_or_default();
+        let size = object.size.unwrap_or_default();
+        let age = now - object.last_modified.map_or(now, |t| t.secs());
+
+        self.older_than.is_none_or(|min_age| age >= min_age)
+            && self.newer_than.is_none_or(|max_age| age < max_age)
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|globs| globs.is_match(key))
+            && self
+                .exclude
+                .as_ref()
+                .is_none_or(|globs| !globs.is_match(key))
+            && self.regex.as_ref().is_none_or(|regex| regex.is_match(key))
+    }
+}
+
+/// Which properties of each object to carry over to its archived copy. Whatever is carried
+/// over, or set, is checked on the copy afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PreserveOptions {
+    /// Keep the source's server-side encryption settings and KMS key
+    pub sse: bool,
+    /// Encrypt copies with this KMS key instead
+    pub sse_kms_key_id: Option<String>,
+    /// Verify tags on the copy; they are carried over either way
+    pub tags: bool,
+    pub acl: bool,
+    pub checksum: bool,
+}
+
+impl PreserveOptions {
+    fn any(&self) -> bool {
+        self.sets_encryption() || self.tags || self.acl || self.checksum
+    }
+
+    fn sets_encryption(&self) -> bool {
+        self.sse || self.sse_kms_key_id.is_some()
+    }
+
+    /// What the copy of an object with these properties should look like.
+    fn expected(&self, source: ObjectProperties) -> ObjectProperties {
+        match &self.sse_kms_key_id {
+            Some(key_id) => ObjectProperties {
+                encryption: Some(ServerSideEncryption::AwsKms),
+                kms_key_id: Some(key_id.clone()),
+                bucket_key_enabled: None,
+                ..source
+            },
+            None => source,
+        }
+    }
+}
+
+/// Properties a copy can change, read from the source before copying and from the copy after.
+/// Tags and ACL grants are only read when they are being preserved.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ObjectProperties {
+    encryption: Option<ServerSideEncryption>,
+    kms_key_id: Option<String>,
+    bucket_key_enabled: Option<bool>,
+    /// Algorithm and value of the stored checksum
+    checksum: Option<(ChecksumAlgorithm, String)>,
+    tags: Vec<(String, String)>,
+    owner: Option<Owner>,
+    grants: Vec<Grant>,
+}
+
+/// Describes every preserved property of `actual` that differs from `expected`.
+fn mismatches(
+    expected: &ObjectProperties,
+    actual: &ObjectProperties,
+    preserve: &PreserveOptions,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let describe = |encryption: &Option<ServerSideEncryption>| {
+        encryption
+            .as_ref()
+            .map_or("none".to_string(), |e| e.as_str().to_string())
+    };
+
+    if preserve.sets_encryption() {
+        if actual.encryption != expected.encryption {
+            problems.push(format!(
+                "encryption is {} instead of {}",
+                describe(&actual.encryption),
+                describe(&expected.encryption)
+            ));
+        } else if !kms_key_matches(expected.kms_key_id.as_deref(), actual.kms_key_id.as_deref()) {
+            problems.push(format!(
+                "KMS key is {} instead of {}",
+                actual.kms_key_id.as_deref().unwrap_or("none"),
+                expected.kms_key_id.as_deref().unwrap_or("none")
+            ));
+        }
+        if preserve.sse
+            && actual.bucket_key_enabled.unwrap_or_default()
+                != expected.bucket_key_enabled.unwrap_or_default()
+        {
+            problems.push("S3 Bucket Key setting differs".to_string());
+        }
+    }
+
+    if preserve.checksum {
+        // Multipart checksums cover the parts, so only the algorithm carries over
+        let matches = match (&expected.checksum, &actual.checksum) {
+            (None, _) => true,
+            (Some((algorithm, value)), Some((actual_algorithm, actual_value))) => {
+                algorithm == actual_algorithm
+                    && (value == actual_value || value.contains('-') || actual_value.contains('-'))
+            }
+            (Some(_), None) => false,
+        };
+        if !matches {
+            problems.push("checksum differs".to_string());
+        }
+    }
+
+    if preserve.tags {
+        let sorted = |tags: &[(String, String)]| {
+            let mut tags = tags.to_vec();
+            tags.sort();
+            tags
+        };
+        if sorted(&actual.tags) != sorted(&expected.tags) {
+            problems.push("tags differ".to_string());
+        }
+    }
+
+    if preserve.acl
+        && (actual.grants.len() != expected.grants.len()
+            || !expected.grants.iter().all(|g| actual.grants.contains(g)))
+    {
+        problems.push("ACL grants differ".to_string());
+    }
+
+    problems
+}
+
+/// S3 reports KMS keys as ARNs, so a key given by ID matches the ARN ending in that ID.
+fn kms_key_matches(expected: Option<&str>, actual: Option<&str>) -> bool {
+    match (expected, actual) {
+        (None, None) => true,
+        (Some(expected), Some(actual)) => {
+            actual == expected || actual.ends_with(&format!("/{}", expected))
+        }
+        _ => false,
+    }
+}
+
+/// A private ACL, which every copy gets anyway. Buckets with ACLs disabled report this and
+/// reject any attempt to set one.
+fn is_default_acl(owner: Option<&Owner>, grants: &[Grant]) -> bool {
+    match (owner.and_then(|owner| owner.id()), grants) {
+        (Some(owner_id), [grant]) => {
+            grant.permission == Some(Permission::FullControl)
+                && grant.grantee.as_ref().and_then(|g| g.id()) == Some(owner_id)
+        }
+        _ => false,
+    }
+}
+
+pub async fn archive_objects(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    prefix: &str,
+    storage_class: &str,
+    options: &ArchiveOptions,
+) -> Result<Progress> {
+    let quiet = options.structured_output().is_some();
+    if !quiet {
+        println!(
+            "Archiving objects with prefix '{}' to {}",
+            prefix, storage_class
+        );
+    }
+
+    let storage_class_enum = match storage_class.to_uppercase().as_str() {
+        "GLACIER" => StorageClass::Glacier,
+        "DEEP_ARCHIVE" => StorageClass::DeepArchive,
+        "GLACIER_IR" => StorageClass::GlacierIr,
+        _ => {
+            println!("Invalid storage class. Use: GLACIER, DEEP_ARCHIVE, or GLACIER_IR");
+            return Ok(Progress::default());
+        }
+    };
+
+    if options.dry_run {
+        dry_run(client, bucket, prefix, storage_class_enum.as_str(), options).await?;
+        return Ok(Progress::default());
+    }
+
+    let context = Arc::new(CopyContext {
+        client: client.clone(),
+        bucket: bucket.to_string(),
+        storage_class: storage_class_enum.clone(),
+        part_size: options.settings.part_size,
+        required_tags: options.filter.tags.clone(),
+        preserve: options.settings.preserve.clone(),
+        retry: options.retry.clone(),
+    });
+
+    let journal = match &options.resume {
+        Some(state) => {
+            if !quiet {
+                println!(
+                    "Resuming from journal '{}' ({} object(s) already done in the current page)",
+                    options.journal.display(),
+                    state.done.len()
+                );
+                for (key, error) in &state.failed {
+                    println!("  Retrying previously failed object: {} ({})", key, error);
+                }
+            }
+            Journal::append(&options.journal)?
+        }
+        None => {
+            if !quiet {
+                println!("Journal: {}", options.journal.display());
+            }
+            Journal::create(
+                &options.journal,
+                bucket,
+                prefix,
+                storage_class_enum.as_str(),
+                &options.settings,
+            )?
+        }
+    };
+
+    let mut queue = Queue {
+        tasks: VecDeque::new(),
+        running: 0,
+        journal,
+        continue_on_error: options.settings.continue_on_error,
+        quiet,
+        tag_filter: !options.filter.tags.is_empty(),
+    };
+    let mut progress = Progress::default();
+
+    let result = tokio::select! {
+        result = list_and_archive(&mut queue, &mut progress, &context, prefix, options) => result,
+        _ = tokio::signal::ctrl_c() => Err(anyhow!("Archive interrupted")),
+    };
+
+    if result.is_err() {
+        queue.interrupt(&mut progress).await?;
+    }
+
+    if !progress.failures.is_empty() {
+        write_failure_report(&options.failure_report, &progress.failures)?;
+    }
+
+    if let Err(e) = result {
+        queue.journal.flush()?;
+        println!(
+            "\nStopped after {} of {} object(s). Resume with: archive --resume {}",
+            progress.done(),
+            progress.selected,
+            queue.journal.path().display()
+        );
+        return Err(e);
+    }
+
+    queue.journal.record(&Entry::Finished)?;
+    queue.journal.flush()?;
+
+    if let Some(format) = options.structured_output() {
+        print!("{}", format.render(&progress)?);
+        return Ok(progress);
+    }
+
+    println!(
+        "\n✓ Archived {} objects to {}",
+        progress.archived, storage_class
+    );
+    if progress.previously_done > 0 {
+        println!(
+            "  Skipped {} object(s) finished by the previous run",
+            progress.previously_done
+        );
+    }
+    if progress.already_in_class > 0 {
+        println!(
+            "  Skipped {} object(s) already in {}",
+            progress.already_in_class, storage_class
+        );
+    }
+    if progress.filtered > 0 {
+        println!(
+            "  {} object(s) did not match the filters",
+            progress.filtered
+        );
+    }
+    if progress.colder_class > 0 {
+        println!(
+            "  ⚠ Left {} object(s) that are already in a colder class than {}",
+            progress.colder_class, storage_class
+        );
+    }
+    if !progress.failures.is_empty() {
+        println!(
+            "  ✗ {} object(s) failed; see {}",
+            progress.failures.len(),
+            options.failure_report.display()
+        );
+    }
+    Ok(progress)
+}
+
+/// Lists objects page by page and queues a copy for each selected one. Copies start as
+/// soon as each page arrives; at most `concurrency` run at once, and results are reported
+/// (and journaled) in listing order. On resume, objects that failed before are retried
+/// first, by key.
+async fn list_and_archive(
+    queue: &mut Queue,
+    progress: &mut Progress,
+    context: &Arc<CopyContext>,
+    prefix: &str,
+    options: &ArchiveOptions,
+) -> Result<()> {
+    let now = DateTime::from(SystemTime::now()).secs();
+    let resume = options.resume.as_ref();
+
+    let mut retried = HashSet::new();
+    for (key, _) in resume.map_or(&[][..], |state| &state.failed) {
+        retried.insert(key.clone());
+        match head_as_listed(context, key).await {
+            Ok(object) => queue_object(queue, progress, context, options, object, now).await?,
+            Err(e) => queue.fail(key, e, progress)?,
+        }
+    }
+
+    let mut continuation_token = resume.and_then(|state| state.continuation_token.clone());
+
+    loop {
+        let page = list_page(
+            &context.client,
+            &context.retry,
+            &context.bucket,
+            prefix,
+            continuation_token.take(),
+        )
+        .await?;
+        let next_token = page.next_continuation_token.clone();
+
+        for object in page.contents.unwrap_or_default() {
+            let Some(key) = object.key.as_deref() else {
+                continue;
+            };
+            if retried.contains(key) {
+                continue;
+            }
+            if resume.is_some_and(|state| state.done.contains(key)) {
+                progress.previously_done += 1;
+                continue;
+            }
+            queue_object(queue, progress, context, options, object, now).await?;
+        }
+
+        match next_token {
+            Some(token) => {
+                queue
+                    .tasks
+                    .push_back((String::new(), Task::Checkpoint(token.clone())));
+                queue.finish_ready(progress).await?;
+                continuation_token = Some(token);
+            }
+            None => break,
+        }
+    }
+
+    while !queue.tasks.is_empty() {
+        queue.finish_next(progress).await?;
+    }
+
+    Ok(())
+}
+
+/// Queues a copy or skip for one listed object, if it passes the listing filters.
+async fn queue_object(
+    queue: &mut Queue,
+    progress: &mut Progress,
+    context: &Arc<CopyContext>,
+    options: &ArchiveOptions,
+    object: Object,
+    now: i64,
+) -> Result<()> {
+    if !options.filter.matches_listing(&object, now) {
+        progress.filtered += 1;
+        return Ok(());
+    }
+    let Some(key) = object.key else {
+        return Ok(());
+    };
+    // With a tag filter, objects are counted once their tags have been checked
+    if !queue.tag_filter {
+        progress.selected += 1;
+    }
+
+    let current_class = object
+        .storage_class
+        .as_ref()
+        .map_or("STANDARD", |class| class.as_str());
+    let task = match disposition(current_class, context.storage_class.as_str()) {
+        Disposition::Copy => {
+            queue.wait_for_slot(options.concurrency, progress).await?;
+            Task::Copy(tokio::spawn(archive_object(
+                context.clone(),
+                key.clone(),
+                object.size.unwrap_or_default(),
+            )))
+        }
+        skip if queue.tag_filter => {
+            queue.wait_for_slot(options.concurrency, progress).await?;
+            Task::Check(
+                skip,
+                current_class.to_string(),
+                tokio::spawn(has_required_tags(context.clone(), key.clone())),
+            )
+        }
+        skip => Task::Skip(skip, current_class.to_string()),
+    };
+    queue.tasks.push_back((key, task));
+    queue.finish_ready(progress).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub objects: u64,
+    pub bytes: i64,
+}
+
+impl Totals {
+    pub fn add(&mut self, size: i64) {
+        self.objects += 1;
+        self.bytes += size;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DryRunSummary {
+    /// Matching objects by their current storage class
+    pub by_class: BTreeMap<String, Totals>,
+    /// Objects that would be copied
+    pub to_copy: Totals,
+    /// S3 requests the copies would take, counting each multipart part
+    pub requests: u64,
+    /// Request cost in USD, when a pricing table was given
+    pub estimated_cost: Option<f64>,
+}
+
+impl DryRunSummary {
+    pub fn add(&mut self, current_class: &str, size: i64, copy: bool, part_size: i64) {
+        self.by_class
+            .entry(current_class.to_string())
+            .or_default()
+            .add(size);
+
+        if copy {
+            self.to_copy.add(size);
+            self.requests += copy_requests(size, part_size);
+        }
+    }
+}
+
+/// Number of requests needed to copy an object: one `copy_object`, or a multipart upload
+/// with one `upload_part_copy` per part.
+pub fn copy_requests(size: i64, part_size: i64) -> u64 {
+    if size > MAX_COPY_OBJECT_SIZE {
+        part_ranges(size, part_size).len() as u64 + 2
+    } else {
+        1
+    }
+}
+
+/// Lists the objects a run would archive and prints totals, without copying anything.
+async fn dry_run(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    prefix: &str,
+    storage_class: &str,
+    options: &ArchiveOptions,
+) -> Result<()> {
+    let now = DateTime::from(SystemTime::now()).secs();
+    let mut summary = DryRunSummary::default();
+    let structured = options.structured_output();
+
+    let retry = &options.retry;
+    let mut continuation_token = None;
+
+    loop {
+        let page = list_page(client, retry, bucket, prefix, continuation_token.take()).await?;
+
+        for object in page.contents.unwrap_or_default() {
+            if !options.filter.matches_listing(&object, now) {
+                continue;
+            }
+            let Some(key) = object.key.as_deref() else {
+                continue;
+            };
+            if !options.filter.tags.is_empty() {
+                let tags = get_tags(client, retry, bucket, key).await?;
+                if !has_tags(&tags, &options.filter.tags) {
+                    continue;
+                }
+            }
+
+            let size = object.size.unwrap_or_default();
+            let current_class = object
+                .storage_class
+                .as_ref()
+                .map_or("STANDARD", |class| class.as_str());
+            let (copy, message) = match disposition(current_class, storage_class) {
+                Disposition::Copy => (
+                    true,
+                    format!(
+                        "  Would archive: {} ({}, {})",
+                        key,
+                        crate::format_bytes(size),
+                        current_class
+                    ),
+                ),
+                Disposition::AlreadyInClass => (
+                    false,
+                    format!("  - Would skip (already {}): {}", current_class, key),
+                ),
+                Disposition::ColderClass => (
+                    false,
+                    format!(
+                        "  ⚠ Would skip (already in colder class {}): {}",
+                        current_class, key
+                    ),
+                ),
+            };
+            if structured.is_none() {
+                println!("{}", message);
+            }
+            summary.add(current_class, size, copy, options.settings.part_size);
+        }
+
+        match page.next_continuation_token {
+            Some(token) => continuation_token = Some(token),
+            None => break,
+        }
+    }
+
+    if let Some(pricing) = &options.pricing {
+        summary.estimated_cost =
+            Some(summary.requests as f64 * pricing.transition_price(storage_class)? / 1000.0);
+    }
+    if let Some(format) = structured {
+        print!("{}", format.render(&summary)?);
+        return Ok(());
+    }
+
+    println!("\nDry run: no objects were copied.\n");
+    println!("Matching objects by current storage class:");
+    if summary.by_class.is_empty() {
+        println!("  (none)");
+    }
+    for (class, totals) in &summary.by_class {
+        println!(
+            "  {:<20} {:>10} object(s)  {:>10}",
+            class,
+            totals.objects,
+            crate::format_bytes(totals.bytes)
+        );
+    }
+
+    println!(
+        "\nWould archive {} object(s) ({}) to {}",
+        summary.to_copy.objects,
+        crate::format_bytes(summary.to_copy.bytes),
+        storage_class
+    );
+    println!("Estimated requests: {}", summary.requests);
+    match summary.estimated_cost {
+        Some(cost) => println!("Estimated request cost: ${:.2}", cost),
+        None => println!("Pass --pricing to estimate the request cost"),
+    }
+
+    Ok(())
+}
+
+pub async fn list_page(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    prefix: &str,
+    continuation_token: Option<String>,
+) -> Result<ListObjectsV2Output> {
+    let request = client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix(prefix)
+        .max_keys(1000)
+        .set_continuation_token(continuation_token);
+
+    retry
+        .send(|| request.clone().send())
+        .await
+        .context("Failed to list objects")
+}
+
+/// What archiving does with an object, given its current storage class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    Copy,
+    AlreadyInClass,
+    ColderClass,
+}
+
+pub fn disposition(current_class: &str, target_class: &str) -> Disposition {
+    // Classes the waterfall doesn't know (e.g. REDUCED_REDUNDANCY) are treated as STANDARD
+    let current = storage_class_rank(current_class).unwrap_or(0);
+    let target = storage_class_rank(target_class).unwrap_or(0);
+
+    match current.cmp(&target) {
+        Ordering::Less => Disposition::Copy,
+        Ordering::Equal => Disposition::AlreadyInClass,
+        Ordering::Greater => Disposition::ColderClass,
+    }
+}
+
+enum Task {
+    /// Copy that resolves to whether the object was archived, or left out by the tag filter
+    Copy(JoinHandle<Result<bool>>),
+    /// Skipped object, with its current storage class
+    Skip(Disposition, String),
+    /// Skipped object whose tags are still being checked against the tag filter
+    Check(Disposition, String, JoinHandle<Result<bool>>),
+    /// Everything before this page token has been processed
+    Checkpoint(String),
+}
+
+impl Task {
+    /// Request still running for this task, if any.
+    fn handle(&self) -> Option<&JoinHandle<Result<bool>>> {
+        match self {
+            Task::Copy(handle) | Task::Check(_, _, handle) => Some(handle),
+            Task::Skip(..) | Task::Checkpoint(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Progress {
+    /// Objects that passed the filters. Without a tag filter this is counted while listing;
+    /// with one, as each object's tags are checked.
+    pub selected: usize,
+    pub archived: usize,
+    pub already_in_class: usize,
+    pub colder_class: usize,
+    pub filtered: usize,
+    pub previously_done: usize,
+    /// Objects that failed with `continue_on_error`
+    pub failures: Vec<Failure>,
+}
+
+impl Progress {
+    fn done(&self) -> usize {
+        self.archived + self.already_in_class + self.colder_class + self.failures.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    pub key: String,
+    /// S3 error code, when the failure came from S3
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl Failure {
+    fn new(key: String, error: &anyhow::Error) -> Self {
+        let code = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<aws_sdk_s3::Error>())
+            .and_then(|e| e.code())
+            .map(str::to_string);
+
+        Failure {
+            key,
+            code,
+            message: format!("{:#}", error),
+        }
+    }
+}
+
+/// Writes failures as CSV if `path` ends in `.csv`, and as JSON otherwise.
+pub fn write_failure_report(path: &Path, failures: &[Failure]) -> Result<()> {
+    let is_csv = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+
+    let contents = if is_csv {
+        let mut csv = String::from("key,code,message\n");
+        for failure in failures {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                csv_field(&failure.key),
+                csv_field(failure.code.as_deref().unwrap_or_default()),
+                csv_field(&failure.message)
+            ));
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(failures)? + "\n"
+    };
+
+    fs::write(path, contents)
+        .with_context(|| format!("Failed to write failure report: {}", path.display()))
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+struct Queue {
+    tasks: VecDeque<(String, Task)>,
+    /// Number of copies and tag checks in `tasks`
+    running: usize,
+    journal: Journal,
+    continue_on_error: bool,
+    /// Leave out per-object messages
+    quiet: bool,
+    /// Objects are only selected once their tags have been checked
+    tag_filter: bool,
+}
+
+impl Queue {
+    /// Reports tasks in order until fewer than `concurrency` requests are running.
+    async fn wait_for_slot(&mut self, concurrency: usize, progress: &mut Progress) -> Result<()> {
+        while self.running >= concurrency {
+            self.finish_next(progress).await?;
+        }
+        self.running += 1;
+        Ok(())
+    }
+
+    /// Waits for the oldest task and reports it.
+    async fn finish_next(&mut self, progress: &mut Progress) -> Result<()> {
+        match self.tasks.pop_front() {
+            Some((key, task)) => self.report(key, task, progress).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn report(&mut self, key: String, task: Task, progress: &mut Progress) -> Result<()> {
+        match task {
+            Task::Copy(handle) => {
+                let Some(archived) = self.join(&key, handle, progress).await? else {
+                    return Ok(());
+                };
+                self.journal.record(&Entry::Done { key: key.clone() })?;
+                if !archived {
+                    progress.filtered += 1;
+                    return Ok(());
+                }
+
+                self.select(progress);
+                progress.archived += 1;
+                if !self.quiet {
+                    println!(
+                        "  ✓ [{}/{}] Archived: {}",
+                        progress.done(),
+                        progress.selected,
+                        key
+                    );
+                }
+            }
+            Task::Check(disposition, current_class, handle) => {
+                let Some(matched) = self.join(&key, handle, progress).await? else {
+                    return Ok(());
+                };
+                if !matched {
+                    self.journal.record(&Entry::Done { key })?;
+                    progress.filtered += 1;
+                    return Ok(());
+                }
+
+                self.select(progress);
+                self.skip(key, disposition, current_class, progress)?;
+            }
+            Task::Skip(disposition, current_class) => {
+                self.skip(key, disposition, current_class, progress)?;
+            }
+            Task::Checkpoint(continuation_token) => {
+                self.journal.record(&Entry::Page { continuation_token })?;
+                self.journal.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for a copy or tag check, returning `None` if it failed and the run goes on.
+    async fn join(
+        &mut self,
+        key: &str,
+        handle: JoinHandle<Result<bool>>,
+        progress: &mut Progress,
+    ) -> Result<Option<bool>> {
+        self.running -= 1;
+        let result = handle
+            .await
+            .with_context(|| format!("Archive task panicked for object: {}", key))
+            .and_then(|result| result);
+
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => self.fail(key, e, progress).map(|()| None),
+        }
+    }
+
+    /// Journals a failed object. The error is returned unless `continue_on_error` is set, in
+    /// which case it's reported and the run goes on.
+    fn fail(&mut self, key: &str, e: anyhow::Error, progress: &mut Progress) -> Result<()> {
+        self.journal.record(&Entry::Failed {
+            key: key.to_string(),
+            error: format!("{:#}", e),
+        })?;
+        if !self.continue_on_error {
+            return Err(e);
+        }
+
+        // Tags of a failed object may not have been read, so it counts as selected
+        self.select(progress);
+        let failure = Failure::new(key.to_string(), &e);
+        if !self.quiet {
+            println!(
+                "  ✗ [{}/{}] Failed: {} ({})",
+                progress.done() + 1,
+                progress.selected,
+                failure.key,
+                failure.code.as_deref().unwrap_or("error")
+            );
+        }
+        progress.failures.push(failure);
+        Ok(())
+    }
+
+    /// Counts an object that passed the tag filter.
+    fn select(&self, progress: &mut Progress) {
+        if self.tag_filter {
+            progress.selected += 1;
+        }
+    }
+
+    fn skip(
+        &mut self,
+        key: String,
+        disposition: Disposition,
+        current_class: String,
+        progress: &mut Progress,
+    ) -> Result<()> {
+        self.journal.record(&Entry::Done { key: key.clone() })?;
+        if disposition == Disposition::ColderClass {
+            progress.colder_class += 1;
+            if !self.quiet {
+                println!(
+                    "  ⚠ [{}/{}] Skipped (already in colder class {}): {}",
+                    progress.done(),
+                    progress.selected,
+                    current_class,
+                    key
+                );
+            }
+        } else {
+            progress.already_in_class += 1;
+            if !self.quiet {
+                println!(
+                    "  - [{}/{}] Skipped (already {}): {}",
+                    progress.done(),
+                    progress.selected,
+                    current_class,
+                    key
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports tasks at the front of the queue that need no waiting: skips, checkpoints and
+    /// copies that have already finished. Keeps the queue short and progress current when
+    /// few objects need copying.
+    async fn finish_ready(&mut self, progress: &mut Progress) -> Result<()> {
+        while let Some((_, task)) = self.tasks.front() {
+            if task.handle().is_some_and(|handle| !handle.is_finished()) {
+                break;
+            }
+            self.finish_next(progress).await?;
+        }
+        Ok(())
+    }
+
+    /// Empties the queue when a run stops early. Skips and finished copies are reported and
+    /// journaled; copies still running are cancelled and left unjournaled, so a resumed run
+    /// picks them up again. Page checkpoints after the first cancelled task are dropped.
+    async fn interrupt(&mut self, progress: &mut Progress) -> Result<()> {
+        // Nothing runs after this, so failures are reported rather than stopping the drain
+        self.continue_on_error = true;
+        let mut cancelled = false;
+
+        while let Some((key, task)) = self.tasks.pop_front() {
+            match task.handle() {
+                Some(handle) if !handle.is_finished() => {
+                    handle.abort();
+                    self.running -= 1;
+                    cancelled = true;
+                }
+                None if cancelled && matches!(task, Task::Checkpoint(_)) => {}
+                _ => self.report(key, task, progress).await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Everything a copy task needs besides the object itself, shared between tasks.
+struct CopyContext {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    storage_class: StorageClass,
+    part_size: i64,
+    required_tags: Vec<(String, String)>,
+    preserve: PreserveOptions,
+    retry: Retry,
+}
+
+/// Archives one object, returning false if it was left out by the tag filter.
+async fn archive_object(context: Arc<CopyContext>, key: String, size: i64) -> Result<bool> {
+    let CopyContext {
+        client,
+        bucket,
+        storage_class,
+        required_tags,
+        preserve,
+        retry,
+        ..
+    } = context.as_ref();
+
+    let source = if preserve.any() {
+        Some(read_properties(client, retry, bucket, &key, preserve).await?)
+    } else {
+        None
+    };
+
+    // Tags already read for verification or the tag filter, so they're fetched only once
+    let mut tags = source
+        .as_ref()
+        .filter(|_| preserve.tags)
+        .map(|source| source.tags.clone());
+    if !required_tags.is_empty() {
+        let tags = match &mut tags {
+            Some(tags) => tags,
+            None => tags.insert(get_tags(client, retry, bucket, &key).await?),
+        };
+        if !has_tags(tags, required_tags) {
+            return Ok(false);
+        }
+    }
+
+    let expected = source.map(|source| preserve.expected(source));
+    let settings = expected
+        .as_ref()
+        .map(|expected| CopySettings::new(expected, preserve))
+        .unwrap_or_default();
+
+    if size > MAX_COPY_OBJECT_SIZE {
+        let tags = match tags {
+            Some(tags) => tags,
+            None => get_tags(client, retry, bucket, &key).await?,
+        };
+        multipart_copy(&context, &key, size, settings, &tags).await?;
+    } else {
+        // Copy object to same location with new storage class
+        let request = client
+            .copy_object()
+            .bucket(bucket)
+            .key(&key)
+            .copy_source(copy_source(bucket, &key))
+            .storage_class(storage_class.clone())
+            .metadata_directive(MetadataDirective::Copy)
+            .tagging_directive(TaggingDirective::Copy)
+            .set_server_side_encryption(settings.encryption)
+            .set_ssekms_key_id(settings.kms_key_id)
+            .set_bucket_key_enabled(settings.bucket_key_enabled)
+            .set_checksum_algorithm(settings.checksum_algorithm);
+        retry
+            .send(|| request.clone().send())
+            .await
+            .map_err(aws_sdk_s3::Error::from)
+            .with_context(|| format!("Failed to archive object: {}", key))?;
+    }
+
+    if let Some(expected) = expected {
+        if preserve.acl && !is_default_acl(expected.owner.as_ref(), &expected.grants) {
+            let request = client
+                .put_object_acl()
+                .bucket(bucket)
+                .key(&key)
+                .access_control_policy(
+                    AccessControlPolicy::builder()
+                        .set_owner(expected.owner.clone())
+                        .set_grants(Some(expected.grants.clone()))
+                        .build(),
+                );
+            retry
+                .send(|| request.clone().send())
+                .await
+                .map_err(aws_sdk_s3::Error::from)
+                .with_context(|| format!("Failed to copy ACL to archived object: {}", key))?;
+        }
+
+        let actual = read_properties(client, retry, bucket, &key, preserve).await?;
+        let problems = mismatches(&expected, &actual, preserve);
+        if !problems.is_empty() {
+            bail!(
+                "Archived copy of {} does not match the source: {}",
+                key,
+                problems.join("; ")
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+/// Encryption and checksum settings to request for a copy; `None` leaves S3's defaults.
+#[derive(Debug, Clone, Default)]
+struct CopySettings {
+    encryption: Option<ServerSideEncryption>,
+    kms_key_id: Option<String>,
+    bucket_key_enabled: Option<bool>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+}
+
+impl CopySettings {
+    fn new(expected: &ObjectProperties, preserve: &PreserveOptions) -> Self {
+        let mut settings = CopySettings::default();
+        if preserve.sets_encryption() {
+            settings.encryption = expected.encryption.clone();
+            settings.kms_key_id = expected.kms_key_id.clone();
+            settings.bucket_key_enabled = expected.bucket_key_enabled;
+        }
+        if preserve.checksum {
+            settings.checksum_algorithm = expected.checksum.as_ref().map(|(a, _)| a.clone());
+        }
+        settings
+    }
+}
+
+/// Looks up an object by key, with the fields a listing would have given.
+async fn head_as_listed(context: &CopyContext, key: &str) -> Result<Object> {
+    let request = context
+        .client
+        .head_object()
+        .bucket(&context.bucket)
+        .key(key);
+    let head = context
+        .retry
+        .send(|| request.clone().send())
+        .await
+        .map_err(aws_sdk_s3::Error::from)
+        .with_context(|| format!("Failed to read object metadata: {}", key))?;
+
+    Ok(Object::builder()
+        .key(key)
+        .set_size(head.content_length)
+        .set_last_modified(head.last_modified)
+        .set_storage_class(
+            head.storage_class
+                .map(|class| ObjectStorageClass::from(class.as_str())),
+        )
+        .build())
+}
+
+async fn read_properties(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    key: &str,
+    preserve: &PreserveOptions,
+) -> Result<ObjectProperties> {
+    let request = client
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .checksum_mode(ChecksumMode::Enabled);
+    let head = retry
+        .send(|| request.clone().send())
+        .await
+        .map_err(aws_sdk_s3::Error::from)
+        .with_context(|| format!("Failed to read object metadata: {}", key))?;
+
+    let mut properties = ObjectProperties {
+        checksum: stored_checksum(&head),
+        encryption: head.server_side_encryption,
+        kms_key_id: head.ssekms_key_id,
+        bucket_key_enabled: head.bucket_key_enabled,
+        ..Default::default()
+    };
+
+    if preserve.tags {
+        properties.tags = get_tags(client, retry, bucket, key).await?;
+    }
+    if preserve.acl {
+        let request = client.get_object_acl().bucket(bucket).key(key);
+        let acl = retry
+            .send(|| request.clone().send())
+            .await
+            .map_err(aws_sdk_s3::Error::from)
+            .with_context(|| format!("Failed to read ACL for object: {}", key))?;
+        properties.owner = acl.owner;
+        properties.grants = acl.grants.unwrap_or_default();
+    }
+
+    Ok(properties)
+}
+
+fn stored_checksum(head: &HeadObjectOutput) -> Option<(ChecksumAlgorithm, String)> {
+    [
+        (ChecksumAlgorithm::Crc32, head.checksum_crc32()),
+        (ChecksumAlgorithm::Crc32C, head.checksum_crc32_c()),
+        (ChecksumAlgorithm::Sha1, head.checksum_sha1()),
+        (ChecksumAlgorithm::Sha256, head.checksum_sha256()),
+    ]
+    .into_iter()
+    .find_map(|(algorithm, value)| value.map(|value| (algorithm, value.to_string())))
+}
+
+/// Checks an object that won't be copied against the tag filter, so it's only reported as
+/// skipped if the filter would have selected it.
+async fn has_required_tags(context: Arc<CopyContext>, key: String) -> Result<bool> {
+    let tags = get_tags(&context.client, &context.retry, &context.bucket, &key).await?;
+    Ok(has_tags(&tags, &context.required_tags))
+}
+
+fn has_tags(tags: &[(String, String)], required: &[(String, String)]) -> bool {
+    required.iter().all(|wanted| tags.contains(wanted))
+}
+
+pub async fn get_tags(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<(String, String)>> {
+    let request = client.get_object_tagging().bucket(bucket).key(key);
+    let tagging = retry
+        .send(|| request.clone().send())
+        .await
+        .map_err(aws_sdk_s3::Error::from)
+        .with_context(|| format!("Failed to get tags for object: {}", key))?;
+
+    Ok(tagging
+        .tag_set()
+        .iter()
+        .map(|tag| (tag.key().to_string(), tag.value().to_string()))
+        .collect())
+}
+
+/// Tags in the URL query form `create_multipart_upload` takes (`key1=value1&key2=value2`).
+fn encode_tagging(tags: &[(String, String)]) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// `x-amz-copy-source` value for copying `key` within `bucket`. The key is sent as a URL
+/// path, so each segment is percent-encoded.
+fn copy_source(bucket: &str, key: &str) -> String {
+    let path = key
+        .split('/')
+        .map(percent_encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}/{}", bucket, path)
+}
+
+/// Percent-encodes everything but unreserved characters.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Copies an object onto itself in parts. Multipart uploads do not carry the source's
+/// metadata or tags over, so they are read first and set on the new upload.
+async fn multipart_copy(
+    context: &CopyContext,
+    key: &str,
+    size: i64,
+    settings: CopySettings,
+    tags: &[(String, String)],
+) -> Result<()> {
+    let CopyContext {
+        client,
+        bucket,
+        storage_class,
+        retry,
+        ..
+    } = context;
+
+    let request = client.head_object().bucket(bucket).key(key);
+    let head = retry
+        .send(|| request.clone().send())
+        .await
+        .map_err(aws_sdk_s3::Error::from)
+        .with_context(|| format!("Failed to read object metadata: {}", key))?;
+
+    let request = client
+        .create_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .storage_class(storage_class.clone())
+        .set_metadata(head.metadata)
+        .set_content_type(head.content_type)
+        .set_content_encoding(head.content_encoding)
+        .set_content_disposition(head.content_disposition)
+        .set_content_language(head.content_language)
+        .set_cache_control(head.cache_control)
+        .set_tagging(if tags.is_empty() {
+            None
+        } else {
+            Some(encode_tagging(tags))
+        })
+        .set_server_side_encryption(settings.encryption)
+        .set_ssekms_key_id(settings.kms_key_id)
+        .set_bucket_key_enabled(settings.bucket_key_enabled)
+        .set_checksum_algorithm(settings.checksum_algorithm);
+    let upload = retry
+        .send(|| request.clone().send())
+        .await
+        .map_err(aws_sdk_s3::Error::from)
+        .with_context(|| format!("Failed to start multipart copy: {}", key))?;
+    let upload_id = upload
+        .upload_id
+        .with_context(|| format!("No upload ID returned for: {}", key))?;
+
+    let result = copy_parts(context, key, &upload_id, head.e_tag, size).await;
+
+    if result.is_err() {
+        // Leave no orphaned parts behind; the copy error is the one worth reporting
+        let request = client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(&upload_id);
+        let _ = retry.send(|| request.clone().send()).await;
+    }
+
+    result.with_context(|| format!("Failed to archive object: {}", key))
+}
+
+async fn copy_parts(
+    context: &CopyContext,
+    key: &str,
+    upload_id: &str,
+    e_tag: Option<String>,
+    size: i64,
+) -> Result<()> {
+    let CopyContext {
+        client,
+        bucket,
+        part_size,
+        retry,
+        ..
+    } = context;
+    let mut parts = Vec::new();
+
+    for (index, (first, last)) in part_ranges(size, *part_size).into_iter().enumerate() {
+        let part_number = index as i32 + 1;
+
+        // Fail rather than stitch together parts of two different versions
+        let request = client
+            .upload_part_copy()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .copy_source(copy_source(bucket, key))
+            .copy_source_range(format!("bytes={}-{}", first, last))
+            .set_copy_source_if_match(e_tag.clone());
+        let output = retry
+            .send(|| request.clone().send())
+            .await
+            .map_err(aws_sdk_s3::Error::from)
+            .with_context(|| format!("Failed to copy part {}", part_number))?;
+
+        // Parts of an upload with a checksum algorithm must list their checksums
+        let mut part = CompletedPart::builder().part_number(part_number);
+        if let Some(result) = output.copy_part_result {
+            part = part
+                .set_e_tag(result.e_tag)
+                .set_checksum_crc32(result.checksum_crc32)
+                .set_checksum_crc32_c(result.checksum_crc32_c)
+                .set_checksum_sha1(result.checksum_sha1)
+                .set_checksum_sha256(result.checksum_sha256);
+        }
+        parts.push(part.build());
+    }
+
+    let request = client
+        .complete_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(parts))
+                .build(),
+        );
+    retry
+        .send(|| request.clone().send())
+        .await
+        .map_err(aws_sdk_s3::Error::from)
+        .context("Failed to complete multipart copy")?;
+
+    Ok(())
+}
+
+/// Inclusive byte ranges covering `size` bytes. The part size grows if needed to stay
+/// within S3's 10,000 part limit.
+pub fn part_ranges(size: i64, part_size: i64) -> Vec<(i64, i64)> {
+    let part_size = part_size.max((size + MAX_PARTS - 1) / MAX_PARTS).max(1);
+
+    (0..size)
+        .step_by(part_size as usize)
+        .map(|first| (first, (first + part_size).min(size) - 1))
+        .collect()
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::error::ErrorMetadata;
+    use aws_sdk_s3::operation::copy_object::CopyObjectError;
+    use aws_sdk_s3::types::{Grantee, Type};
+
+    fn listed(key: &str, size: i64, age_days: i64, now: i64) -> Object {
+        Object::builder()
+            .key(key)
+            .size(size)
+            .last_modified(DateTime::from_secs(now - age_days * 86_400))
+            .build()
+    }
+
+    #[test]
+    fn test_object_filter() {
+        let now = 1_800_000_000;
+        let filter = ArchiveSettings {
+            older_than: Some(180 * 86_400),
+            min_size: Some(1024),
+            include: vec!["media/**/*.mp4".to_string(), "media/**/*.mov".to_string()],
+            exclude: vec!["media/keep/**".to_string()],
+            regex: Some(r"/20(19|20)/".to_string()),
+            ..Default::default()
+        }
+        .filter()
+        .unwrap();
+
+        assert!(filter.matches_listing(&listed("media/raw/2019/a.mp4", 4096, 200, now), now));
+        // Too recent, too small, wrong extension, excluded, wrong year
+        assert!(!filter.matches_listing(&listed("media/raw/2019/a.mp4", 4096, 30, now), now));
+        assert!(!filter.matches_listing(&listed("media/raw/2019/a.mp4", 10, 200, now), now));
+        assert!(!filter.matches_listing(&listed("media/raw/2019/a.txt", 4096, 200, now), now));
+        assert!(!filter.matches_listing(&listed("media/keep/2019/a.mp4", 4096, 200, now), now));
+        assert!(!filter.matches_listing(&listed("media/raw/2021/a.mp4", 4096, 200, now), now));
+
+        let recent = ObjectFilter {
+            newer_than: Some(7 * 86_400),
+            max_size: Some(1024),
+            ..Default::default()
+        };
+        assert!(recent.matches_listing(&listed("a", 1024, 1, now), now));
+        assert!(!recent.matches_listing(&listed("a", 1024, 8, now), now));
+        assert!(!recent.matches_listing(&listed("a", 1025, 1, now), now));
+    }
+
+    #[test]
+    fn test_conflicting_flag() {
+        let recorded = ArchiveSettings {
+            min_size: Some(1024),
+            include: vec!["*.log".to_string()],
+            part_size: DEFAULT_PART_SIZE,
+            continue_on_error: true,
+            ..Default::default()
+        };
+
+        // Flags that aren't given, or match the journal, are fine
+        let same = ArchiveSettings {
+            part_size: DEFAULT_PART_SIZE,
+            ..Default::default()
+        };
+        assert_eq!(same.conflicting_flag(&recorded), None);
+        assert_eq!(recorded.conflicting_flag(&recorded), None);
+
+        let wider = ArchiveSettings {
+            min_size: Some(1),
+            ..same.clone()
+        };
+        assert_eq!(wider.conflicting_flag(&recorded), Some("min-size"));
+
+        let tagged = ArchiveSettings {
+            tags: vec![("team".to_string(), "data".to_string())],
+            ..same
+        };
+        assert_eq!(tagged.conflicting_flag(&recorded), Some("tag"));
+    }
+
+    #[test]
+    fn test_disposition() {
+        assert_eq!(disposition("STANDARD", "GLACIER"), Disposition::Copy);
+        assert_eq!(disposition("STANDARD_IA", "GLACIER_IR"), Disposition::Copy);
+        assert_eq!(
+            disposition("GLACIER", "GLACIER"),
+            Disposition::AlreadyInClass
+        );
+        assert_eq!(
+            disposition("DEEP_ARCHIVE", "GLACIER"),
+            Disposition::ColderClass
+        );
+        assert_eq!(
+            disposition("GLACIER", "GLACIER_IR"),
+            Disposition::ColderClass
+        );
+        assert_eq!(
+            disposition("REDUCED_REDUNDANCY", "GLACIER"),
+            Disposition::Copy
+        );
+    }
+
+    #[test]
+    fn test_dry_run_summary() {
+        let part_size = 512 << 20;
+        let mut summary = DryRunSummary::default();
+        summary.add("STANDARD", 1024, true, part_size);
+        summary.add("STANDARD", 6 << 30, true, part_size);
+        summary.add("GLACIER", 2048, false, part_size);
+
+        assert_eq!(
+            summary.by_class["STANDARD"],
+            Totals {
+                objects: 2,
+                bytes: 1024 + (6 << 30)
+            }
+        );
+        assert_eq!(summary.by_class["GLACIER"].objects, 1);
+        assert_eq!(summary.to_copy.objects, 2);
+        // One copy_object, plus create + 12 parts + complete for the 6GB object
+        assert_eq!(summary.requests, 1 + 14);
+    }
+
+    fn grant(id: &str, permission: Permission) -> Grant {
+        Grant::builder()
+            .grantee(
+                Grantee::builder()
+                    .id(id)
+                    .r#type(Type::CanonicalUser)
+                    .build()
+                    .unwrap(),
+            )
+            .permission(permission)
+            .build()
+    }
+
+    #[test]
+    fn test_preserved_properties_are_checked() {
+        let source = ObjectProperties {
+            encryption: Some(ServerSideEncryption::AwsKms),
+            kms_key_id: Some("arn:aws:kms:us-east-1:111122223333:key/abcd".to_string()),
+            checksum: Some((ChecksumAlgorithm::Sha256, "c2hh".to_string())),
+            tags: vec![("team".to_string(), "data".to_string())],
+            ..Default::default()
+        };
+        let all = PreserveOptions {
+            sse: true,
+            tags: true,
+            acl: true,
+            checksum: true,
+            ..Default::default()
+        };
+        assert!(mismatches(&source, &source, &all).is_empty());
+
+        // Bucket default encryption and no tags
+        let defaults = ObjectProperties {
+            encryption: Some(ServerSideEncryption::Aes256),
+            checksum: Some((ChecksumAlgorithm::Crc32, "Y3Jj".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(mismatches(&source, &defaults, &all).len(), 3);
+        assert!(mismatches(&source, &defaults, &PreserveOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_new_kms_key() {
+        let preserve = PreserveOptions {
+            sse_kms_key_id: Some("abcd".to_string()),
+            ..Default::default()
+        };
+        let expected = preserve.expected(ObjectProperties::default());
+        let copy = ObjectProperties {
+            encryption: Some(ServerSideEncryption::AwsKms),
+            kms_key_id: Some("arn:aws:kms:us-east-1:111122223333:key/abcd".to_string()),
+            ..Default::default()
+        };
+        assert!(mismatches(&expected, &copy, &preserve).is_empty());
+
+        let other_key = ObjectProperties {
+            kms_key_id: Some("arn:aws:kms:us-east-1:111122223333:key/ef01".to_string()),
+            ..copy
+        };
+        assert_eq!(mismatches(&expected, &other_key, &preserve).len(), 1);
+    }
+
+    #[test]
+    fn test_multipart_checksum_keeps_algorithm_only() {
+        let preserve = PreserveOptions {
+            checksum: true,
+            ..Default::default()
+        };
+        let checksum = |algorithm, value: &str| ObjectProperties {
+            checksum: Some((algorithm, value.to_string())),
+            ..Default::default()
+        };
+        let source = checksum(ChecksumAlgorithm::Crc32C, "abc=-40");
+        assert!(mismatches(
+            &source,
+            &checksum(ChecksumAlgorithm::Crc32C, "def=-12"),
+            &preserve
+        )
+        .is_empty());
+        assert!(!mismatches(
+            &source,
+            &checksum(ChecksumAlgorithm::Sha1, "def=-12"),
+            &preserve
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_is_default_acl() {
+        let owner = Owner::builder().id("owner").build();
+        let private = [grant("owner", Permission::FullControl)];
+        assert!(is_default_acl(Some(&owner), &private));
+
+        let shared = [
+            grant("owner", Permission::FullControl),
+            grant("partner", Permission::Read),
+        ];
+        assert!(!is_default_acl(Some(&owner), &shared));
+        assert!(!is_default_acl(None, &private));
+    }
+
+    #[test]
+    fn test_encode_tagging() {
+        let tags = vec![
+            ("team".to_string(), "data".to_string()),
+            ("path".to_string(), "a/b c&d=e".to_string()),
+        ];
+        assert_eq!(encode_tagging(&tags), "team=data&path=a%2Fb%20c%26d%3De");
+    }
+
+    #[test]
+    fn test_copy_source() {
+        assert_eq!(copy_source("b", "logs/a.log"), "b/logs/a.log");
+        assert_eq!(
+            copy_source("b", "media/raw/My Film+Cut (1).mov"),
+            "b/media/raw/My%20Film%2BCut%20%281%29.mov"
+        );
+        assert_eq!(
+            copy_source("b", "100%/a?b#c/é"),
+            "b/100%25/a%3Fb%23c/%C3%A9"
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("logs/a.log"), "logs/a.log");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_failure_code() {
+        let metadata = ErrorMetadata::builder()
+            .code("AccessDenied")
+            .message("Access Denied")
+            .build();
+        let error = anyhow::Error::new(aws_sdk_s3::Error::from(CopyObjectError::generic(metadata)))
+            .context("Failed to archive object: a");
+        let failure = Failure::new("a".to_string(), &error);
+        assert_eq!(failure.code.as_deref(), Some("AccessDenied"));
+    }
+
+    #[test]
+    fn test_part_ranges() {
+        assert_eq!(part_ranges(10, 4), vec![(0, 3), (4, 7), (8, 9)]);
+        assert_eq!(part_ranges(8, 4), vec![(0, 3), (4, 7)]);
+        assert_eq!(part_ranges(0, 4), vec![]);
+    }
+
+    #[test]
+    fn test_part_ranges_respect_part_limit() {
+        let size = 6 << 40;
+        let ranges = part_ranges(size, MIN_PART_SIZE);
+
+        assert!(ranges.len() as i64 <= MAX_PARTS);
+        assert_eq!(ranges.last().unwrap().1, size - 1);
+    }
+}
@@ -0,0 +1,533 @@
+// Compliance audit: checks buckets' lifecycle rules against rules that must exist.
+//
+// A required-rules file lists requirements, each checked on its own, e.g.
+//
+//   requirements:
+//     - name: expire-logs
+//       prefix: logs/
+//       max_expiration_days: 400
+//     - name: abort-multipart
+//       max_abort_multipart_days: 7
+//     - name: archive-media
+//       prefix: media/
+//       transition:
+//         storage_class: GLACIER
+//         max_days: 90
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::buckets::Selected;
+use crate::policy::{FilterSpec, PolicyFormat, RuleSpec, RuleStatus};
+use crate::validate::storage_class_rank;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequiredPolicy {
+    pub requirements: Vec<Requirement>,
+}
+
+/// Actions a bucket must apply to every object under `prefix`. Each limit that is set is a
+/// separate check, which any rule covering the prefix can satisfy.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Requirement {
+    pub name: String,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub max_expiration_days: Option<i32>,
+    #[serde(default)]
+    pub max_noncurrent_expiration_days: Option<i32>,
+    #[serde(default)]
+    pub max_abort_multipart_days: Option<i32>,
+    #[serde(default)]
+    pub transition: Option<RequiredTransition>,
+}
+
+/// A transition to `storage_class`, or a colder class, within `max_days`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequiredTransition {
+    pub storage_class: String,
+    pub max_days: i32,
+}
+
+impl RequiredPolicy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read required-rules file: {}", path.display()))?;
+
+        Self::parse(PolicyFormat::from_path(path)?, &contents)
+            .with_context(|| format!("Failed to parse required-rules file: {}", path.display()))
+    }
+
+    pub fn parse(format: PolicyFormat, contents: &str) -> Result<Self> {
+        let policy: RequiredPolicy = match format {
+            PolicyFormat::Yaml => serde_yaml::from_str(contents)?,
+            PolicyFormat::Json => serde_json::from_str(contents)?,
+            PolicyFormat::Toml => toml::from_str(contents)?,
+        };
+
+        for requirement in &policy.requirements {
+            if requirement.checks().is_empty() {
+                bail!("requirement '{}' has nothing to check", requirement.name);
+            }
+            if let Some(transition) = &requirement.transition {
+                if storage_class_rank(&transition.storage_class).is_none() {
+                    bail!(
+                        "requirement '{}' has unknown storage class '{}'",
+                        requirement.name,
+                        transition.storage_class
+                    );
+                }
+            }
+        }
+        Ok(policy)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Check {
+    Expiration(i32),
+    NoncurrentExpiration(i32),
+    AbortMultipart(i32),
+    Transition {
+        storage_class: String,
+        max_days: i32,
+    },
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Expiration(days) => write!(f, "expiration ≤ {} days", days),
+            Check::NoncurrentExpiration(days) => {
+                write!(f, "noncurrent version expiration ≤ {} days", days)
+            }
+            Check::AbortMultipart(days) => write!(f, "abort multipart ≤ {} days", days),
+            Check::Transition {
+                storage_class,
+                max_days,
+            } => write!(f, "transition to {} ≤ {} days", storage_class, max_days),
+        }
+    }
+}
+
+impl Check {
+    fn max_days(&self) -> i32 {
+        match self {
+            Check::Expiration(days)
+            | Check::NoncurrentExpiration(days)
+            | Check::AbortMultipart(days) => *days,
+            Check::Transition { max_days, .. } => *max_days,
+        }
+    }
+
+    /// Days after which `rule` takes the checked action: `None` if the rule doesn't take
+    /// it, `Some(None)` if it does so on a date rather than after a number of days.
+    fn days(&self, rule: &RuleSpec) -> Option<Option<i32>> {
+        match self {
+            Check::Expiration(_) => {
+                let expiration = rule.expiration.as_ref()?;
+                if expiration.days.is_none() && expiration.date.is_none() {
+                    return None;
+                }
+                Some(expiration.days)
+            }
+            Check::NoncurrentExpiration(_) => rule
+                .noncurrent_version_expiration
+                .as_ref()
+                .map(|e| Some(e.noncurrent_days)),
+            Check::AbortMultipart(_) => rule
+                .abort_incomplete_multipart_upload
+                .as_ref()
+                .map(|a| Some(a.days_after_initiation)),
+            Check::Transition { storage_class, .. } => {
+                let required = storage_class_rank(storage_class)?;
+                let transitions: Vec<Option<i32>> = rule
+                    .transitions
+                    .iter()
+                    .filter(|t| storage_class_rank(&t.storage_class).is_some_and(|r| r >= required))
+                    .map(|t| t.days)
+                    .collect();
+                if transitions.is_empty() {
+                    return None;
+                }
+                Some(transitions.into_iter().flatten().min())
+            }
+        }
+    }
+}
+
+impl Requirement {
+    fn checks(&self) -> Vec<Check> {
+        let mut checks = Vec::new();
+        if let Some(days) = self.max_expiration_days {
+            checks.push(Check::Expiration(days));
+        }
+        if let Some(days) = self.max_noncurrent_expiration_days {
+            checks.push(Check::NoncurrentExpiration(days));
+        }
+        if let Some(days) = self.max_abort_multipart_days {
+            checks.push(Check::AbortMultipart(days));
+        }
+        if let Some(transition) = &self.transition {
+            checks.push(Check::Transition {
+                storage_class: transition.storage_class.clone(),
+                max_days: transition.max_days,
+            });
+        }
+        checks
+    }
+}
+
+/// A rule applies to every object under `prefix` if its own prefix contains it and it
+/// doesn't narrow the objects further by tags or size.
+fn covers(filter: &FilterSpec, prefix: &str) -> bool {
+    prefix.starts_with(filter.prefix.as_deref().unwrap_or_default())
+        && filter.tags.is_empty()
+        && filter.object_size_greater_than.is_none()
+        && filter.object_size_less_than.is_none()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Compliant,
+    /// No rule covering the prefix takes the action
+    Missing,
+    /// Only disabled rules take the action
+    Disabled,
+    /// An enabled rule takes the action, but later than required or on a fixed date
+    Weaker,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Status::Compliant => "compliant",
+            Status::Missing => "missing",
+            Status::Disabled => "disabled",
+            Status::Weaker => "weaker",
+        };
+        f.pad(status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub requirement: String,
+    pub prefix: String,
+    pub check: String,
+    pub status: Status,
+    /// Rule that satisfies the check, or comes closest to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketReport {
+    pub bucket: String,
+    pub compliant: bool,
+    pub findings: Vec<Finding>,
+    /// Set when the bucket's rules couldn't be read; the bucket counts as non-compliant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditReport {
+    pub compliant: bool,
+    pub buckets: Vec<BucketReport>,
+}
+
+fn evaluate(requirement: &Requirement, check: &Check, rules: &[RuleSpec]) -> Finding {
+    let mut finding = Finding {
+        requirement: requirement.name.clone(),
+        prefix: requirement.prefix.clone(),
+        check: check.to_string(),
+        status: Status::Missing,
+        rule_id: None,
+        detail: None,
+    };
+
+    let mut weaker = None;
+    let mut disabled = None;
+    for rule in rules
+        .iter()
+        .filter(|r| covers(&r.filter, &requirement.prefix))
+    {
+        let Some(days) = check.days(rule) else {
+            continue;
+        };
+        match rule.status {
+            RuleStatus::Enabled if days.is_some_and(|days| days <= check.max_days()) => {
+                finding.status = Status::Compliant;
+                finding.rule_id = Some(rule.id.clone());
+                return finding;
+            }
+            RuleStatus::Enabled => {
+                weaker.get_or_insert((rule, days));
+            }
+            RuleStatus::Disabled => {
+                disabled.get_or_insert(rule);
+            }
+        }
+    }
+
+    if let Some((rule, days)) = weaker {
+        finding.status = Status::Weaker;
+        finding.rule_id = Some(rule.id.clone());
+        finding.detail = Some(match days {
+            Some(days) => format!("rule '{}' uses {} days", rule.id, days),
+            None => format!("rule '{}' uses a fixed date", rule.id),
+        });
+    } else if let Some(rule) = disabled {
+        finding.status = Status::Disabled;
+        finding.rule_id = Some(rule.id.clone());
+        finding.detail = Some(format!("rule '{}' is disabled", rule.id));
+    }
+    finding
+}
+
+/// Checks one bucket's rules against every requirement.
+pub fn audit_rules(bucket: &str, policy: &RequiredPolicy, rules: &[RuleSpec]) -> BucketReport {
+    let findings: Vec<Finding> = policy
+        .requirements
+        .iter()
+        .flat_map(|requirement| {
+            requirement
+                .checks()
+                .into_iter()
+                .map(move |check| evaluate(requirement, &check, rules))
+        })
+        .collect();
+
+    BucketReport {
+        bucket: bucket.to_string(),
+        compliant: findings.iter().all(|f| f.status == Status::Compliant),
+        findings,
+        error: None,
+    }
+}
+
+pub async fn audit_buckets(
+    client: &aws_sdk_s3::Client,
+    buckets: Vec<Selected>,
+    policy: &RequiredPolicy,
+) -> AuditReport {
+    let mut reports = Vec::new();
+    for selected in buckets {
+        let bucket = &selected.name;
+        let rules = match selected.error {
+            Some(e) => Err(e),
+            None => crate::get_lifecycle_rules(client, bucket).await,
+        };
+        let report = match rules {
+            Ok(rules) => match rules
+                .iter()
+                .map(RuleSpec::try_from)
+                .collect::<Result<Vec<_>>>()
+            {
+                Ok(rules) => audit_rules(bucket, policy, &rules),
+                Err(e) => error_report(bucket, e),
+            },
+            Err(e) => error_report(bucket, e),
+        };
+        reports.push(report);
+    }
+
+    AuditReport {
+        compliant: reports.iter().all(|r| r.compliant),
+        buckets: reports,
+    }
+}
+
+/// Report for a bucket whose rules couldn't be read or understood.
+fn error_report(bucket: &str, e: anyhow::Error) -> BucketReport {
+    BucketReport {
+        bucket: bucket.to_string(),
+        compliant: false,
+        findings: Vec::new(),
+        error: Some(format!("{:#}", e)),
+    }
+}
+
+pub fn print_report(report: &AuditReport) {
+    for bucket in &report.buckets {
+        if let Some(error) = &bucket.error {
+            println!("✗ {}: {}", bucket.bucket, error);
+            continue;
+        }
+        let failing: Vec<&Finding> = bucket
+            .findings
+            .iter()
+            .filter(|f| f.status != Status::Compliant)
+            .collect();
+        if failing.is_empty() {
+            println!("✓ {}: compliant", bucket.bucket);
+            continue;
+        }
+
+        println!(
+            "✗ {}: {} of {} check(s) not met",
+            bucket.bucket,
+            failing.len(),
+            bucket.findings.len()
+        );
+        for finding in failing {
+            let prefix = if finding.prefix.is_empty() {
+                "(all objects)"
+            } else {
+                &finding.prefix
+            };
+            print!(
+                "    {:<9} {}: {} on {}",
+                finding.status, finding.requirement, finding.check, prefix
+            );
+            match &finding.detail {
+                Some(detail) => println!(" ({})", detail),
+                None => println!(),
+            }
+        }
+    }
+
+    let failed = report.buckets.iter().filter(|b| !b.compliant).count();
+    println!(
+        "\n{} of {} bucket(s) compliant",
+        report.buckets.len() - failed,
+        report.buckets.len()
+    );
+}
+
+pub fn write_report(path: &Path, report: &AuditReport) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(report)? + "\n")
+        .with_context(|| format!("Failed to write audit report: {}", path.display()))
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn required() -> RequiredPolicy {
+        RequiredPolicy::parse(
+            PolicyFormat::Yaml,
+            r#"
+requirements:
+  - name: expire-logs
+    prefix: logs/app/
+    max_expiration_days: 400
+  - name: abort-multipart
+    max_abort_multipart_days: 7
+  - name: archive-media
+    prefix: media/
+    transition:
+      storage_class: GLACIER
+      max_days: 90
+"#,
+        )
+        .unwrap()
+    }
+
+    fn rules(yaml: &str) -> Vec<RuleSpec> {
+        PolicyFormat::Yaml.parse(yaml).unwrap().rules
+    }
+
+    fn statuses(report: &BucketReport) -> Vec<(&str, Status)> {
+        report
+            .findings
+            .iter()
+            .map(|f| (f.requirement.as_str(), f.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_compliant_bucket() {
+        let rules = rules(
+            r#"
+rules:
+  - id: logs
+    filter:
+      prefix: logs/
+    expiration:
+      days: 365
+  - id: cleanup
+    abort_incomplete_multipart_upload:
+      days_after_initiation: 3
+  - id: media
+    filter:
+      prefix: media/
+    transitions:
+      - days: 30
+        storage_class: DEEP_ARCHIVE
+"#,
+        );
+        let report = audit_rules("b", &required(), &rules);
+        assert!(report.compliant, "{:?}", report.findings);
+        assert_eq!(report.findings[0].rule_id.as_deref(), Some("logs"));
+    }
+
+    #[test]
+    fn test_missing_disabled_and_weaker() {
+        let rules = rules(
+            r#"
+rules:
+  - id: logs
+    filter:
+      prefix: logs/
+    expiration:
+      days: 730
+  - id: cleanup
+    status: Disabled
+    abort_incomplete_multipart_upload:
+      days_after_initiation: 3
+  - id: media
+    filter:
+      prefix: media/
+      tags:
+        archive: "true"
+    transitions:
+      - days: 30
+        storage_class: GLACIER
+"#,
+        );
+        let report = audit_rules("b", &required(), &rules);
+        assert!(!report.compliant);
+        assert_eq!(
+            statuses(&report),
+            vec![
+                ("expire-logs", Status::Weaker),
+                ("abort-multipart", Status::Disabled),
+                // Tag filters don't cover every object under the prefix
+                ("archive-media", Status::Missing),
+            ]
+        );
+        assert_eq!(
+            report.findings[0].detail.as_deref(),
+            Some("rule 'logs' uses 730 days")
+        );
+    }
+
+    #[test]
+    fn test_transition_to_warmer_class_does_not_count() {
+        let rules = rules(
+            "rules:\n  - id: ia\n    transitions:\n      - days: 30\n        storage_class: STANDARD_IA\n",
+        );
+        let report = audit_rules("b", &required(), &rules);
+        assert_eq!(report.findings[2].status, Status::Missing);
+    }
+
+    #[test]
+    fn test_requirement_needs_a_check() {
+        let result = RequiredPolicy::parse(PolicyFormat::Yaml, "requirements:\n  - name: empty\n");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,222 @@
+// Runs lifecycle commands on many buckets at once, chosen by name pattern or bucket tags.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+
+use anyhow::{anyhow, bail, Context, Result};
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use globset::GlobMatcher;
+
+use crate::output;
+
+/// Buckets a command runs on.
+pub enum Buckets {
+    One(String),
+    Selected(BucketSelector),
+}
+
+/// Chooses buckets whose name matches `pattern` and that carry all of `tags`.
+#[derive(Debug, Clone, Default)]
+pub struct BucketSelector {
+    pub pattern: Option<GlobMatcher>,
+    pub tags: Vec<(String, String)>,
+}
+
+impl BucketSelector {
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(name))
+    }
+
+    pub fn matches_tags(&self, tags: &BTreeMap<String, String>) -> bool {
+        self.tags
+            .iter()
+            .all(|(key, value)| tags.get(key) == Some(value))
+    }
+}
+
+/// A bucket to run on. `error` says why it can't be used: it's in a region the client
+/// isn't configured for, or its tags couldn't be read to check it against the selector.
+pub struct Selected {
+    pub name: String,
+    pub error: Option<anyhow::Error>,
+}
+
+/// The account's buckets that match `selector`, sorted by name.
+pub async fn select(
+    client: &aws_sdk_s3::Client,
+    selector: &BucketSelector,
+) -> Result<Vec<Selected>> {
+    let output = client
+        .list_buckets()
+        .send()
+        .await
+        .context("Failed to list buckets")?;
+
+    let client_region = client.config().region().map(|region| region.to_string());
+    // `list_buckets` returns buckets from every region, and each bucket only answers in its own
+    let mut regional_clients = HashMap::new();
+
+    let mut selected = Vec::new();
+    for bucket in output.buckets.unwrap_or_default() {
+        let Some(name) = bucket.name else {
+            continue;
+        };
+        if !selector.matches_name(&name) {
+            continue;
+        }
+
+        let other_region = bucket
+            .bucket_region
+            .filter(|r| Some(r) != client_region.as_ref());
+        // Tags cost a request per bucket, so they are only fetched when filtering on them
+        if !selector.tags.is_empty() {
+            let client = match &other_region {
+                Some(region) => regional_clients
+                    .entry(region.clone())
+                    .or_insert_with(|| regional_client(client, region)),
+                None => client,
+            };
+            match bucket_tags(client, &name).await {
+                Ok(tags) if !selector.matches_tags(&tags) => continue,
+                Ok(_) => {}
+                Err(e) => {
+                    selected.push(Selected {
+                        name,
+                        error: Some(e),
+                    });
+                    continue;
+                }
+            }
+        }
+
+        let error = other_region.map(|region| {
+            anyhow!(
+                "bucket is in {}; run with AWS_REGION={} to include it",
+                region,
+                region
+            )
+        });
+        selected.push(Selected { name, error });
+    }
+
+    selected.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(selected)
+}
+
+fn regional_client(client: &aws_sdk_s3::Client, region: &str) -> aws_sdk_s3::Client {
+    let config = client
+        .config()
+        .to_builder()
+        .region(Region::new(region.to_string()))
+        .build();
+    aws_sdk_s3::Client::from_conf(config)
+}
+
+async fn bucket_tags(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+) -> Result<BTreeMap<String, String>> {
+    match client.get_bucket_tagging().bucket(bucket).send().await {
+        Ok(output) => Ok(output
+            .tag_set
+            .into_iter()
+            .map(|tag| (tag.key, tag.value))
+            .collect()),
+        Err(e) if e.code() == Some("NoSuchTagSet") => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to get tags for bucket '{}'", bucket)),
+    }
+}
+
+/// The buckets to run on.
+pub async fn resolve(client: &aws_sdk_s3::Client, buckets: &Buckets) -> Result<Vec<Selected>> {
+    match buckets {
+        Buckets::One(bucket) => Ok(vec![Selected {
+            name: bucket.clone(),
+            error: None,
+        }]),
+        Buckets::Selected(selector) => select(client, selector).await,
+    }
+}
+
+/// Runs `command` on each bucket. A single `--bucket` runs as it always has. Selected
+/// buckets run one after another: a failure, or a bucket that can't be used, is reported
+/// and the rest still run, then each bucket's result is summarised. `command` returns a
+/// one-line result for the summary.
+pub async fn for_each<F, Fut>(
+    client: &aws_sdk_s3::Client,
+    buckets: &Buckets,
+    mut command: F,
+) -> Result<()>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let selector = match buckets {
+        Buckets::One(bucket) => return command(bucket.clone()).await.map(|_| ()),
+        Buckets::Selected(selector) => selector,
+    };
+
+    let selected = select(client, selector).await?;
+    if selected.is_empty() {
+        println!("No buckets match the selection.");
+        return Ok(());
+    }
+    println!("Selected {} bucket(s)", selected.len());
+
+    let mut rows = Vec::new();
+    let mut failed = 0;
+    for bucket in selected {
+        println!("\n=== {} ===", bucket.name);
+        let result = match bucket.error {
+            Some(e) => Err(e),
+            None => command(bucket.name.clone()).await,
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                println!("✗ {:#}", e);
+                failed += 1;
+                format!("failed: {}", e)
+            }
+        };
+        rows.push(vec![bucket.name, result]);
+    }
+
+    println!("\nSummary:");
+    print!("{}", output::table(&["BUCKET", "RESULT"], &rows));
+
+    if failed > 0 {
+        bail!("{} of {} bucket(s) failed", failed, rows.len());
+    }
+    Ok(())
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use globset::Glob;
+
+    #[test]
+    fn test_selector_matches() {
+        let selector = BucketSelector {
+            pattern: Some(Glob::new("logs-*").unwrap().compile_matcher()),
+            tags: vec![("env".to_string(), "prod".to_string())],
+        };
+        assert!(selector.matches_name("logs-eu"));
+        assert!(!selector.matches_name("media"));
+
+        let mut tags = BTreeMap::new();
+        assert!(!selector.matches_tags(&tags));
+        tags.insert("env".to_string(), "prod".to_string());
+        tags.insert("team".to_string(), "data".to_string());
+        assert!(selector.matches_tags(&tags));
+        tags.insert("env".to_string(), "dev".to_string());
+        assert!(!selector.matches_tags(&tags));
+
+        assert!(BucketSelector::default().matches_name("anything"));
+    }
+}
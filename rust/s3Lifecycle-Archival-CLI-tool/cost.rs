@@ -0,0 +1,319 @@
+// Monthly storage cost projections for lifecycle policies, using a local pricing table.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::simulate::{Action, Prediction, SimObject};
+use crate::validate::minimum_storage_days;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+/// Projections bill storage in 30-day months, as the S3 pricing page does.
+pub const SECONDS_PER_MONTH: i64 = 30 * SECONDS_PER_DAY;
+const BYTES_PER_GB: f64 = (1u64 << 30) as f64;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PricingTable {
+    pub regions: BTreeMap<String, RegionPricing>,
+}
+
+/// Prices for one region, keyed by storage class name.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegionPricing {
+    /// USD per GB-month
+    pub storage: BTreeMap<String, f64>,
+    /// USD per 1,000 lifecycle transition requests into the class
+    #[serde(default)]
+    pub transition: BTreeMap<String, f64>,
+    /// USD per GB retrieved from the class
+    #[serde(default)]
+    pub retrieval: BTreeMap<String, f64>,
+}
+
+impl PricingTable {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pricing file: {}", path.display()))?;
+
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse pricing file: {}", path.display()))
+    }
+
+    pub fn region(&self, region: &str) -> Result<&RegionPricing> {
+        self.regions.get(region).ok_or_else(|| {
+            anyhow!(
+                "No pricing for region '{}'. Available: {}",
+                region,
+                self.regions.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
+}
+
+impl RegionPricing {
+    fn storage_price(&self, storage_class: &str) -> Result<f64> {
+        self.storage
+            .get(storage_class)
+            .copied()
+            .ok_or_else(|| anyhow!("No storage price for class '{}'", storage_class))
+    }
+
+    pub fn transition_price(&self, storage_class: &str) -> Result<f64> {
+        self.transition
+            .get(storage_class)
+            .copied()
+            .ok_or_else(|| anyhow!("No transition request price for class '{}'", storage_class))
+    }
+
+    fn retrieval_price(&self, storage_class: &str) -> f64 {
+        self.retrieval
+            .get(storage_class)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Count and cost of a kind of charge, keyed by storage class in `Projection`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Charge {
+    pub objects: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projection {
+    /// Total cost of each month, including request charges and penalties
+    pub monthly: Vec<f64>,
+    pub storage: f64,
+    /// Lifecycle transition requests, by target class
+    pub transitions: BTreeMap<String, Charge>,
+    /// Objects leaving a class before its minimum storage duration, by class left
+    pub early_deletions: BTreeMap<String, Charge>,
+    /// Cost of retrieving every remaining object once at the end of the period
+    pub retrieval_at_end: f64,
+}
+
+impl Projection {
+    pub fn total(&self) -> f64 {
+        self.monthly.iter().sum()
+    }
+
+    /// Bills `gb` in `storage_class` from `from` to `to`, split across the months it spans.
+    fn add_storage(
+        &mut self,
+        pricing: &RegionPricing,
+        storage_class: &str,
+        gb: f64,
+        from: i64,
+        to: i64,
+        start: i64,
+    ) -> Result<()> {
+        if to <= from {
+            return Ok(());
+        }
+        let price = pricing.storage_price(storage_class)?;
+
+        let mut cursor = from;
+        while cursor < to {
+            let month = ((cursor - start) / SECONDS_PER_MONTH) as usize;
+            let month_end = (start + (month as i64 + 1) * SECONDS_PER_MONTH).min(to);
+            let cost = gb * price * (month_end - cursor) as f64 / SECONDS_PER_MONTH as f64;
+
+            self.monthly[month] += cost;
+            self.storage += cost;
+            cursor = month_end;
+        }
+
+        Ok(())
+    }
+}
+
+/// Projects the cost of `objects` over `months` months from `start`, applying the predicted
+/// lifecycle actions. Actions that are already due take effect at `start`, and each object
+/// is assumed to have entered its current class when it was last modified.
+pub fn project(
+    objects: &[SimObject],
+    predictions: &[Prediction],
+    pricing: &RegionPricing,
+    start: i64,
+    months: u32,
+) -> Result<Projection> {
+    let end = start + i64::from(months) * SECONDS_PER_MONTH;
+    let mut projection = Projection {
+        monthly: vec![0.0; months as usize],
+        ..Default::default()
+    };
+
+    for (object, prediction) in objects.iter().zip(predictions) {
+        let gb = object.size as f64 / BYTES_PER_GB;
+        let mut storage_class = object.storage_class.clone();
+        let mut entered = object.last_modified;
+        let mut cursor = start;
+        let mut expired = false;
+
+        for event in &prediction.events {
+            let at = event.at.max(start);
+            if at >= end {
+                break;
+            }
+
+            projection.add_storage(pricing, &storage_class, gb, cursor, at, start)?;
+            cursor = at;
+            let month = ((at - start) / SECONDS_PER_MONTH) as usize;
+
+            // Leaving a class early is billed as if the object stayed the full minimum
+            let stored_days = (at - entered) / SECONDS_PER_DAY;
+            let minimum_days = i64::from(minimum_storage_days(&storage_class));
+            if stored_days < minimum_days {
+                let cost = gb
+                    * pricing.storage_price(&storage_class)?
+                    * (minimum_days - stored_days) as f64
+                    / 30.0;
+                let charge = projection
+                    .early_deletions
+                    .entry(storage_class.clone())
+                    .or_default();
+                charge.objects += 1;
+                charge.cost += cost;
+                projection.monthly[month] += cost;
+            }
+
+            match &event.action {
+                Action::Transition(target) => {
+                    let cost = pricing.transition_price(target)? / 1000.0;
+                    let charge = projection.transitions.entry(target.clone()).or_default();
+                    charge.objects += 1;
+                    charge.cost += cost;
+                    projection.monthly[month] += cost;
+
+                    storage_class = target.clone();
+                    entered = at;
+                }
+                Action::Expire => {
+                    expired = true;
+                    break;
+                }
+            }
+        }
+
+        if !expired {
+            projection.add_storage(pricing, &storage_class, gb, cursor, end, start)?;
+            projection.retrieval_at_end += gb * pricing.retrieval_price(&storage_class);
+        }
+    }
+
+    Ok(projection)
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{parse_date, PolicyFormat};
+    use crate::simulate::predict;
+
+    const GB: i64 = 1 << 30;
+
+    fn pricing() -> RegionPricing {
+        let table: PricingTable = toml::from_str(
+            r#"
+[regions.test.storage]
+STANDARD = 0.02
+GLACIER = 0.004
+
+[regions.test.transition]
+GLACIER = 0.05
+
+[regions.test.retrieval]
+GLACIER = 0.01
+"#,
+        )
+        .unwrap();
+        table.region("test").unwrap().clone()
+    }
+
+    fn object(size: i64, created: &str) -> SimObject {
+        SimObject {
+            key: "data/file".to_string(),
+            size,
+            last_modified: parse_date(created).unwrap().secs(),
+            storage_class: "STANDARD".to_string(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    fn project_yaml(yaml: &str, objects: &[SimObject], start: &str, months: u32) -> Projection {
+        let rules = PolicyFormat::Yaml.parse(yaml).unwrap().rules;
+        let predictions: Vec<_> = objects.iter().map(|o| predict(&rules, o)).collect();
+        let start = parse_date(start).unwrap().secs();
+        project(objects, &predictions, &pricing(), start, months).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_no_rules_bills_standard_storage() {
+        let objects = vec![object(10 * GB, "2026-01-01")];
+        let projection = project_yaml("rules: []", &objects, "2026-01-01", 3);
+
+        assert_eq!(projection.monthly.len(), 3);
+        for month in &projection.monthly {
+            assert_close(*month, 0.2);
+        }
+        assert!(projection.transitions.is_empty());
+    }
+
+    #[test]
+    fn test_transition_changes_price_and_charges_request() {
+        let yaml = "rules:\n  - id: r\n    transitions:\n      - days: 30\n        storage_class: GLACIER\n";
+        let objects = vec![object(10 * GB, "2026-01-01")];
+        let projection = project_yaml(yaml, &objects, "2026-01-01", 2);
+
+        assert_close(projection.monthly[0], 0.2);
+        assert_close(projection.monthly[1], 0.04 + 0.05 / 1000.0);
+        assert_eq!(projection.transitions["GLACIER"].objects, 1);
+        assert_close(projection.retrieval_at_end, 0.1);
+    }
+
+    #[test]
+    fn test_early_deletion_penalty() {
+        let yaml = r#"
+rules:
+  - id: r
+    transitions:
+      - days: 30
+        storage_class: GLACIER
+    expiration:
+      days: 60
+"#;
+        let objects = vec![object(10 * GB, "2026-01-01")];
+        let projection = project_yaml(yaml, &objects, "2026-01-01", 3);
+
+        // Expired after 30 of GLACIER's 90 minimum days: 60 more days are billed
+        let penalty = &projection.early_deletions["GLACIER"];
+        assert_eq!(penalty.objects, 1);
+        assert_close(penalty.cost, 10.0 * 0.004 * 2.0);
+        assert_close(projection.monthly[2], penalty.cost);
+        assert_close(projection.retrieval_at_end, 0.0);
+    }
+
+    #[test]
+    fn test_missing_region() {
+        let table = PricingTable {
+            regions: BTreeMap::new(),
+        };
+        assert!(table.region("us-east-1").is_err());
+    }
+}
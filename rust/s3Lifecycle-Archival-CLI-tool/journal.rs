@@ -0,0 +1,293 @@
+// Checkpoint journal for archive runs, so an interrupted run can be resumed.
+//
+// The journal is a JSON Lines file. Each listing page that has been fully processed is
+// recorded with the continuation token of the next page, followed by the keys finished
+// (or failed) within that page. Failed keys stay failed across pages until a later run
+// finishes them.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::ArchiveSettings;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    Start {
+        bucket: String,
+        prefix: String,
+        storage_class: String,
+        settings: Box<ArchiveSettings>,
+    },
+    /// Every object listed before `continuation_token` has been processed
+    Page {
+        continuation_token: String,
+    },
+    Done {
+        key: String,
+    },
+    Failed {
+        key: String,
+        error: String,
+    },
+    Finished,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl Journal {
+    /// Starts a new journal, replacing any file at `path`.
+    pub fn create(
+        path: &Path,
+        bucket: &str,
+        prefix: &str,
+        storage_class: &str,
+        settings: &ArchiveSettings,
+    ) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create journal: {}", path.display()))?;
+        let mut journal = Journal {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+        };
+
+        journal.record(&Entry::Start {
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            storage_class: storage_class.to_string(),
+            settings: Box::new(settings.clone()),
+        })?;
+        journal.flush()?;
+        Ok(journal)
+    }
+
+    /// Reopens an existing journal to continue writing to it.
+    pub fn append(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open journal: {}", path.display()))?;
+        let mut journal = Journal {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+        };
+
+        // Terminate a partial last line left by a killed run; blank lines are ignored
+        writeln!(journal.writer)
+            .with_context(|| format!("Failed to write journal: {}", path.display()))?;
+        Ok(journal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, entry: &Entry) -> Result<()> {
+        let line = serde_json::to_string(entry)?;
+        writeln!(self.writer, "{}", line)
+            .with_context(|| format!("Failed to write journal: {}", self.path.display()))
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write journal: {}", self.path.display()))
+    }
+}
+
+/// Where an interrupted run stopped, read back from its journal.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResumeState {
+    pub bucket: String,
+    pub prefix: String,
+    pub storage_class: String,
+    /// Filters and copy options the run was started with
+    pub settings: ArchiveSettings,
+    /// Token of the first listing page that was not fully processed
+    pub continuation_token: Option<String>,
+    /// Keys already finished within that page
+    pub done: HashSet<String>,
+    /// Keys that failed on any page and have not been finished since, with their errors
+    pub failed: Vec<(String, String)>,
+    pub finished: bool,
+}
+
+impl ResumeState {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read journal: {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Failed to parse journal: {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+        let mut state = ResumeState::default();
+
+        for (index, line) in lines.iter().enumerate() {
+            let entry = match serde_json::from_str::<Entry>(line) {
+                Ok(entry) => entry,
+                // A run killed mid-write can leave a partial last line
+                Err(_) if index == lines.len() - 1 => break,
+                Err(e) => return Err(e).context(format!("Invalid entry on line {}", index + 1)),
+            };
+
+            match entry {
+                Entry::Start {
+                    bucket,
+                    prefix,
+                    storage_class,
+                    settings,
+                } if index == 0 => {
+                    state.bucket = bucket;
+                    state.prefix = prefix;
+                    state.storage_class = storage_class;
+                    state.settings = *settings;
+                }
+                Entry::Start { .. } => bail!("Unexpected start entry on line {}", index + 1),
+                _ if index == 0 => bail!("Journal does not begin with a start entry"),
+                Entry::Page { continuation_token } => {
+                    state.continuation_token = Some(continuation_token);
+                    state.done.clear();
+                }
+                Entry::Done { key } => {
+                    state.failed.retain(|(failed, _)| *failed != key);
+                    state.done.insert(key);
+                }
+                Entry::Failed { key, error } => {
+                    state.failed.retain(|(failed, _)| *failed != key);
+                    state.failed.push((key, error));
+                }
+                Entry::Finished => state.finished = true,
+            }
+        }
+
+        if lines.is_empty() {
+            bail!("Journal is empty");
+        }
+        Ok(state)
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal(entries: &[Entry]) -> String {
+        entries
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap() + "\n")
+            .collect()
+    }
+
+    fn start() -> Entry {
+        Entry::Start {
+            bucket: "b".to_string(),
+            prefix: "logs/".to_string(),
+            storage_class: "GLACIER".to_string(),
+            settings: Box::new(ArchiveSettings {
+                include: vec!["**/*.log".to_string()],
+                min_size: Some(1024),
+                part_size: 512 << 20,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn done(key: &str) -> Entry {
+        Entry::Done {
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resume_from_last_page() {
+        let contents = journal(&[
+            start(),
+            done("logs/1"),
+            Entry::Page {
+                continuation_token: "t1".to_string(),
+            },
+            done("logs/2"),
+            Entry::Failed {
+                key: "logs/3".to_string(),
+                error: "AccessDenied".to_string(),
+            },
+        ]);
+
+        let state = ResumeState::parse(&contents).unwrap();
+        assert_eq!(state.bucket, "b");
+        assert_eq!(state.prefix, "logs/");
+        assert_eq!(state.settings.include, ["**/*.log"]);
+        assert_eq!(state.settings.min_size, Some(1024));
+        assert_eq!(state.continuation_token.as_deref(), Some("t1"));
+        assert_eq!(state.done, HashSet::from(["logs/2".to_string()]));
+        assert_eq!(state.failed.len(), 1);
+        assert!(!state.finished);
+    }
+
+    #[test]
+    fn test_failures_are_kept_across_pages() {
+        let contents = journal(&[
+            start(),
+            Entry::Failed {
+                key: "logs/1".to_string(),
+                error: "AccessDenied".to_string(),
+            },
+            Entry::Failed {
+                key: "logs/2".to_string(),
+                error: "InternalError".to_string(),
+            },
+            Entry::Page {
+                continuation_token: "t1".to_string(),
+            },
+            done("logs/2"),
+            Entry::Failed {
+                key: "logs/1".to_string(),
+                error: "SlowDown".to_string(),
+            },
+            Entry::Page {
+                continuation_token: "t2".to_string(),
+            },
+        ]);
+
+        let state = ResumeState::parse(&contents).unwrap();
+        assert_eq!(state.continuation_token.as_deref(), Some("t2"));
+        assert!(state.done.is_empty());
+        assert_eq!(
+            state.failed,
+            vec![("logs/1".to_string(), "SlowDown".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_partial_last_line_is_ignored() {
+        let mut contents = journal(&[start(), done("a")]);
+        contents.push_str("{\"done\":{\"ke");
+
+        let state = ResumeState::parse(&contents).unwrap();
+        assert_eq!(state.done.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_journal() {
+        assert!(ResumeState::parse("").is_err());
+        assert!(ResumeState::parse(&journal(&[done("a"), start()])).is_err());
+        assert!(ResumeState::parse("garbage\n\"finished\"\n").is_err());
+    }
+
+    #[test]
+    fn test_finished_journal() {
+        let state = ResumeState::parse(&journal(&[start(), done("a"), Entry::Finished])).unwrap();
+        assert!(state.finished);
+    }
+}
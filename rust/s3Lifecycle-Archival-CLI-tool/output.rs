@@ -0,0 +1,212 @@
+// Output formats for commands that scripts consume: aligned tables for people, JSON and
+// YAML for machines. Structured output uses the same schema as policy files.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::policy::{FilterSpec, RuleSpec, RuleStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    /// JSON and YAML replace all other output on stdout, so scripts can parse it.
+    pub fn is_structured(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Yaml)
+    }
+
+    /// Renders `value` as JSON or YAML. Commands without a table layout fall back to YAML.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        let contents = match self {
+            OutputFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            OutputFormat::Table | OutputFormat::Yaml => serde_yaml::to_string(value)?,
+        };
+        Ok(contents)
+    }
+}
+
+/// Lays out rows under a header with each column padded to its widest cell.
+pub fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string() + "\n"
+    };
+
+    let headers: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    let underline: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let mut output = line(&headers) + &line(&underline);
+    for row in rows {
+        output += &line(row);
+    }
+    output
+}
+
+pub fn rules_table(rules: &[RuleSpec]) -> String {
+    let rows: Vec<Vec<String>> = rules
+        .iter()
+        .map(|rule| {
+            vec![
+                rule.id.clone(),
+                match rule.status {
+                    RuleStatus::Enabled => "Enabled".to_string(),
+                    RuleStatus::Disabled => "Disabled".to_string(),
+                },
+                describe_filter(&rule.filter),
+                describe_transitions(rule),
+                describe_expiration(rule),
+            ]
+        })
+        .collect();
+
+    table(
+        &["ID", "STATUS", "FILTER", "TRANSITIONS", "EXPIRATION"],
+        &rows,
+    )
+}
+
+fn describe_filter(filter: &FilterSpec) -> String {
+    let mut parts = Vec::new();
+    if let Some(prefix) = &filter.prefix {
+        parts.push(format!("prefix={}", prefix));
+    }
+    for (key, value) in &filter.tags {
+        parts.push(format!("tag:{}={}", key, value));
+    }
+    if let Some(size) = filter.object_size_greater_than {
+        parts.push(format!("size>{}", size));
+    }
+    if let Some(size) = filter.object_size_less_than {
+        parts.push(format!("size<{}", size));
+    }
+
+    if parts.is_empty() {
+        "-".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+fn describe_transitions(rule: &RuleSpec) -> String {
+    let mut parts: Vec<String> = rule
+        .transitions
+        .iter()
+        .map(|t| match (&t.date, t.days) {
+            (Some(date), _) => format!("{}→{}", date, t.storage_class),
+            (None, days) => format!("{}d→{}", days.unwrap_or_default(), t.storage_class),
+        })
+        .collect();
+    parts.extend(
+        rule.noncurrent_version_transitions
+            .iter()
+            .map(|t| format!("noncurrent {}d→{}", t.noncurrent_days, t.storage_class)),
+    );
+
+    if parts.is_empty() {
+        "-".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+fn describe_expiration(rule: &RuleSpec) -> String {
+    let mut parts = Vec::new();
+    if let Some(expiration) = &rule.expiration {
+        if let Some(days) = expiration.days {
+            parts.push(format!("{}d", days));
+        }
+        if let Some(date) = &expiration.date {
+            parts.push(date.clone());
+        }
+        if expiration.expired_object_delete_marker == Some(true) {
+            parts.push("delete markers".to_string());
+        }
+    }
+    if let Some(expiration) = &rule.noncurrent_version_expiration {
+        parts.push(format!("noncurrent {}d", expiration.noncurrent_days));
+    }
+
+    if parts.is_empty() {
+        "-".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::PolicyFormat;
+
+    #[test]
+    fn test_table_aligns_columns() {
+        let rows = vec![
+            vec!["a".to_string(), "1".to_string()],
+            vec!["long-name".to_string(), "22".to_string()],
+        ];
+        assert_eq!(
+            table(&["NAME", "N"], &rows),
+            "NAME       N\n---------  --\na          1\nlong-name  22\n"
+        );
+    }
+
+    #[test]
+    fn test_rules_table() {
+        let policy = PolicyFormat::Yaml
+            .parse(
+                r#"
+rules:
+  - id: logs
+    filter:
+      prefix: logs/
+    transitions:
+      - days: 30
+        storage_class: STANDARD_IA
+      - days: 90
+        storage_class: GLACIER
+    expiration:
+      days: 365
+  - id: tmp
+    status: Disabled
+    expiration:
+      days: 7
+"#,
+            )
+            .unwrap();
+
+        let table = rules_table(&policy.rules);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2]
+            .starts_with("logs  Enabled   prefix=logs/  30d→STANDARD_IA, 90d→GLACIER  365d"));
+        assert_eq!(
+            lines[3],
+            "tmp   Disabled  -             -                             7d"
+        );
+    }
+
+    #[test]
+    fn test_structured_output_is_policy_schema() {
+        let policy = PolicyFormat::Yaml
+            .parse("rules:\n  - id: tmp\n    expiration:\n      days: 7\n")
+            .unwrap();
+        let json = OutputFormat::Json.render(&policy).unwrap();
+        assert_eq!(PolicyFormat::Json.parse(&json).unwrap(), policy);
+    }
+}
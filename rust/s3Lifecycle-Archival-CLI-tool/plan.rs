@@ -0,0 +1,206 @@
+// Terraform-style plan of the changes between a bucket's current and desired lifecycle rules.
+
+use anyhow::Result;
+use aws_sdk_s3::types::LifecycleRule;
+
+use crate::policy::RuleSpec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleChange {
+    Add(RuleSpec),
+    Remove(RuleSpec),
+    Update { id: String, diffs: Vec<FieldDiff> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    pub changes: Vec<RuleChange>,
+}
+
+impl Plan {
+    /// Compares rules by ID: desired rules missing from `current` are added, current rules
+    /// missing from `desired` are removed, and rules present in both are diffed field by field.
+    pub fn new(current: &[LifecycleRule], desired: &[LifecycleRule]) -> Result<Self> {
+        let current = current
+            .iter()
+            .map(RuleSpec::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let desired = desired
+            .iter()
+            .map(RuleSpec::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let mut changes = Vec::new();
+
+        for rule in &desired {
+            match current.iter().find(|r| r.id == rule.id) {
+                None => changes.push(RuleChange::Add(rule.clone())),
+                Some(existing) => {
+                    let diffs = diff_fields(existing, rule);
+                    if !diffs.is_empty() {
+                        changes.push(RuleChange::Update {
+                            id: rule.id.clone(),
+                            diffs,
+                        });
+                    }
+                }
+            }
+        }
+
+        for rule in &current {
+            if !desired.iter().any(|r| r.id == rule.id) {
+                changes.push(RuleChange::Remove(rule.clone()));
+            }
+        }
+
+        Ok(Plan { changes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn print(&self, bucket: &str) {
+        println!("Lifecycle plan for bucket '{}':", bucket);
+
+        if self.is_empty() {
+            println!("\nNo changes. Lifecycle configuration is up to date.");
+            return;
+        }
+
+        let (mut added, mut changed, mut removed) = (0, 0, 0);
+
+        for change in &self.changes {
+            match change {
+                RuleChange::Add(rule) => {
+                    added += 1;
+                    println!("\n  + rule \"{}\"", rule.id);
+                    for (field, value) in describe_fields(rule) {
+                        println!("      {:<24} {}", format!("{}:", field), value);
+                    }
+                }
+                RuleChange::Remove(rule) => {
+                    removed += 1;
+                    println!("\n  - rule \"{}\"", rule.id);
+                    for (field, value) in describe_fields(rule) {
+                        println!("      {:<24} {}", format!("{}:", field), value);
+                    }
+                }
+                RuleChange::Update { id, diffs } => {
+                    changed += 1;
+                    println!("\n  ~ rule \"{}\"", id);
+                    for diff in diffs {
+                        println!("      {}:", diff.field);
+                        println!("        - {}", diff.before);
+                        println!("        + {}", diff.after);
+                    }
+                }
+            }
+        }
+
+        println!(
+            "\nPlan: {} to add, {} to change, {} to remove.",
+            added, changed, removed
+        );
+    }
+}
+
+fn diff_fields(before: &RuleSpec, after: &RuleSpec) -> Vec<FieldDiff> {
+    describe_fields(before)
+        .into_iter()
+        .zip(describe_fields(after))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, before), (_, after))| FieldDiff {
+            field,
+            before,
+            after,
+        })
+        .collect()
+}
+
+fn describe_fields(rule: &RuleSpec) -> Vec<(&'static str, String)> {
+    vec![
+        ("status", format!("{:?}", rule.status)),
+        ("filter", rule.filter.to_string()),
+        ("transitions", join(&rule.transitions)),
+        ("expiration", describe_option(rule.expiration.as_ref())),
+        (
+            "noncurrent transitions",
+            join(&rule.noncurrent_version_transitions),
+        ),
+        (
+            "noncurrent expiration",
+            describe_option(rule.noncurrent_version_expiration.as_ref()),
+        ),
+        (
+            "abort multipart upload",
+            describe_option(rule.abort_incomplete_multipart_upload.as_ref()),
+        ),
+    ]
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    if items.is_empty() {
+        "(none)".to_string()
+    } else {
+        items
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn describe_option<T: ToString>(item: Option<&T>) -> String {
+    item.map(ToString::to_string)
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::PolicyFormat;
+
+    fn rules(yaml: &str) -> Vec<LifecycleRule> {
+        PolicyFormat::Yaml.parse(yaml).unwrap().to_rules().unwrap()
+    }
+
+    #[test]
+    fn test_identical_rules_produce_empty_plan() {
+        let current = rules("rules:\n  - id: a\n    expiration:\n      days: 30\n");
+        assert!(Plan::new(&current, &current).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_add_remove_and_update() {
+        let current = rules(
+            "rules:\n  - id: keep\n    expiration:\n      days: 30\n  - id: old\n    expiration:\n      days: 5\n",
+        );
+        let desired = rules(
+            "rules:\n  - id: keep\n    expiration:\n      days: 60\n  - id: new\n    expiration:\n      days: 5\n",
+        );
+
+        let plan = Plan::new(&current, &desired).unwrap();
+        assert_eq!(plan.changes.len(), 3);
+        assert_eq!(
+            plan.changes[0],
+            RuleChange::Update {
+                id: "keep".to_string(),
+                diffs: vec![FieldDiff {
+                    field: "expiration",
+                    before: "30d".to_string(),
+                    after: "60d".to_string(),
+                }],
+            }
+        );
+        assert!(matches!(&plan.changes[1], RuleChange::Add(rule) if rule.id == "new"));
+        assert!(matches!(&plan.changes[2], RuleChange::Remove(rule) if rule.id == "old"));
+    }
+}
@@ -0,0 +1,824 @@
+// Typed model of a lifecycle policy file and its conversion to S3 lifecycle rules.
+//
+// A policy file holds the complete set of rules for a bucket, e.g.
+//
+//   rules:
+//     - id: archive-logs
+//       filter:
+//         prefix: logs/
+//       transitions:
+//         - days: 30
+//           storage_class: STANDARD_IA
+//         - days: 90
+//           storage_class: GLACIER
+//       expiration:
+//         days: 365
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::{
+    AbortIncompleteMultipartUpload, ExpirationStatus, LifecycleExpiration, LifecycleRule,
+    LifecycleRuleAndOperator, LifecycleRuleFilter, NoncurrentVersionExpiration,
+    NoncurrentVersionTransition, Tag, Transition, TransitionStorageClass,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LifecyclePolicy {
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSpec {
+    pub id: String,
+    #[serde(default)]
+    pub status: RuleStatus,
+    #[serde(default, skip_serializing_if = "FilterSpec::is_empty")]
+    pub filter: FilterSpec,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transitions: Vec<TransitionSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<ExpirationSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub noncurrent_version_transitions: Vec<NoncurrentTransitionSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noncurrent_version_expiration: Option<NoncurrentExpirationSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abort_incomplete_multipart_upload: Option<AbortMultipartSpec>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleStatus {
+    #[default]
+    Enabled,
+    Disabled,
+}
+
+/// Object filter. More than one predicate is combined into an `And` filter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_size_greater_than: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_size_less_than: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<i32>,
+    /// Transition date (YYYY-MM-DD, midnight UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    pub storage_class: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpirationSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<i32>,
+    /// Expiration date (YYYY-MM-DD, midnight UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expired_object_delete_marker: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoncurrentTransitionSpec {
+    pub noncurrent_days: i32,
+    pub storage_class: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_noncurrent_versions: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoncurrentExpirationSpec {
+    pub noncurrent_days: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_noncurrent_versions: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AbortMultipartSpec {
+    pub days_after_initiation: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PolicyFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl PolicyFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "yaml" | "yml" => Ok(PolicyFormat::Yaml),
+            "json" => Ok(PolicyFormat::Json),
+            "toml" => Ok(PolicyFormat::Toml),
+            _ => bail!(
+                "Unsupported policy file '{}' (use .yaml, .json or .toml)",
+                path.display()
+            ),
+        }
+    }
+
+    pub fn parse(self, contents: &str) -> Result<LifecyclePolicy> {
+        let policy = match self {
+            PolicyFormat::Yaml => serde_yaml::from_str(contents)?,
+            PolicyFormat::Json => serde_json::from_str(contents)?,
+            PolicyFormat::Toml => toml::from_str(contents)?,
+        };
+        Ok(policy)
+    }
+
+    pub fn render(self, policy: &LifecyclePolicy) -> Result<String> {
+        let contents = match self {
+            PolicyFormat::Yaml => serde_yaml::to_string(policy)?,
+            PolicyFormat::Json => serde_json::to_string_pretty(policy)? + "\n",
+            PolicyFormat::Toml => toml::to_string_pretty(policy)?,
+        };
+        Ok(contents)
+    }
+}
+
+impl LifecyclePolicy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let format = PolicyFormat::from_path(path)?;
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read policy file: {}", path.display()))?;
+
+        format
+            .parse(&contents)
+            .with_context(|| format!("Failed to parse policy file: {}", path.display()))
+    }
+
+    pub fn from_rules(rules: &[LifecycleRule]) -> Result<Self> {
+        Ok(LifecyclePolicy {
+            rules: rules
+                .iter()
+                .map(RuleSpec::try_from)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn to_rules(&self) -> Result<Vec<LifecycleRule>> {
+        self.rules.iter().map(RuleSpec::to_rule).collect()
+    }
+}
+
+impl RuleSpec {
+    pub fn to_rule(&self) -> Result<LifecycleRule> {
+        self.build_rule()
+            .with_context(|| format!("Invalid lifecycle rule '{}'", self.id))
+    }
+
+    /// Like `to_rule`, for callers that report the rule ID themselves.
+    pub fn build_rule(&self) -> Result<LifecycleRule> {
+        if !self.has_actions() {
+            bail!("rule must have at least one transition, expiration or cleanup action");
+        }
+
+        // S3 rejects rules that mix days-based and date-based actions
+        let uses_days = self.transitions.iter().any(|t| t.days.is_some())
+            || self.expiration.as_ref().is_some_and(|e| e.days.is_some());
+        let uses_dates = self.transitions.iter().any(|t| t.date.is_some())
+            || self.expiration.as_ref().is_some_and(|e| e.date.is_some());
+        if uses_days && uses_dates {
+            bail!("rule cannot mix days-based and date-based transitions or expiration");
+        }
+
+        let mut rule_builder = LifecycleRule::builder()
+            .id(&self.id)
+            .filter(self.filter.to_filter()?)
+            .status(match self.status {
+                RuleStatus::Enabled => ExpirationStatus::Enabled,
+                RuleStatus::Disabled => ExpirationStatus::Disabled,
+            });
+
+        for transition in &self.transitions {
+            rule_builder = rule_builder.transitions(transition.to_transition()?);
+        }
+
+        if let Some(expiration) = &self.expiration {
+            rule_builder = rule_builder.expiration(expiration.to_expiration()?);
+        }
+
+        for transition in &self.noncurrent_version_transitions {
+            rule_builder = rule_builder.noncurrent_version_transitions(
+                NoncurrentVersionTransition::builder()
+                    .noncurrent_days(transition.noncurrent_days)
+                    .storage_class(parse_storage_class(&transition.storage_class)?)
+                    .set_newer_noncurrent_versions(transition.newer_noncurrent_versions)
+                    .build(),
+            );
+        }
+
+        if let Some(expiration) = &self.noncurrent_version_expiration {
+            rule_builder = rule_builder.noncurrent_version_expiration(
+                NoncurrentVersionExpiration::builder()
+                    .noncurrent_days(expiration.noncurrent_days)
+                    .set_newer_noncurrent_versions(expiration.newer_noncurrent_versions)
+                    .build(),
+            );
+        }
+
+        if let Some(abort) = &self.abort_incomplete_multipart_upload {
+            rule_builder = rule_builder.abort_incomplete_multipart_upload(
+                AbortIncompleteMultipartUpload::builder()
+                    .days_after_initiation(abort.days_after_initiation)
+                    .build(),
+            );
+        }
+
+        Ok(rule_builder.build()?)
+    }
+}
+
+impl RuleSpec {
+    pub fn has_actions(&self) -> bool {
+        !self.transitions.is_empty()
+            || self.expiration.is_some()
+            || !self.noncurrent_version_transitions.is_empty()
+            || self.noncurrent_version_expiration.is_some()
+            || self.abort_incomplete_multipart_upload.is_some()
+    }
+}
+
+impl TryFrom<&LifecycleRule> for RuleSpec {
+    type Error = anyhow::Error;
+
+    fn try_from(rule: &LifecycleRule) -> Result<Self> {
+        let filter = match &rule.filter {
+            Some(filter) => FilterSpec::try_from(filter).with_context(|| {
+                format!(
+                    "Rule '{}' has a filter this tool can't read",
+                    rule.id.as_deref().unwrap_or_default()
+                )
+            })?,
+            // Rules created before filters existed carry a top-level prefix instead
+            #[allow(deprecated)]
+            None => FilterSpec {
+                prefix: rule.prefix.clone().filter(|p| !p.is_empty()),
+                ..Default::default()
+            },
+        };
+
+        Ok(RuleSpec {
+            id: rule.id.clone().unwrap_or_default(),
+            status: match rule.status {
+                ExpirationStatus::Disabled => RuleStatus::Disabled,
+                _ => RuleStatus::Enabled,
+            },
+            filter,
+            transitions: rule
+                .transitions()
+                .iter()
+                .map(|t| TransitionSpec {
+                    days: t.days,
+                    date: t.date.as_ref().map(format_date),
+                    storage_class: storage_class_name(t.storage_class.as_ref()),
+                })
+                .collect(),
+            expiration: rule.expiration.as_ref().map(|e| ExpirationSpec {
+                days: e.days,
+                date: e.date.as_ref().map(format_date),
+                expired_object_delete_marker: e.expired_object_delete_marker,
+            }),
+            noncurrent_version_transitions: rule
+                .noncurrent_version_transitions()
+                .iter()
+                .map(|t| NoncurrentTransitionSpec {
+                    noncurrent_days: t.noncurrent_days.unwrap_or_default(),
+                    storage_class: storage_class_name(t.storage_class.as_ref()),
+                    newer_noncurrent_versions: t.newer_noncurrent_versions,
+                })
+                .collect(),
+            noncurrent_version_expiration: rule.noncurrent_version_expiration.as_ref().map(|e| {
+                NoncurrentExpirationSpec {
+                    noncurrent_days: e.noncurrent_days.unwrap_or_default(),
+                    newer_noncurrent_versions: e.newer_noncurrent_versions,
+                }
+            }),
+            abort_incomplete_multipart_upload: rule.abort_incomplete_multipart_upload.as_ref().map(
+                |a| AbortMultipartSpec {
+                    days_after_initiation: a.days_after_initiation.unwrap_or_default(),
+                },
+            ),
+        })
+    }
+}
+
+impl FilterSpec {
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_none()
+            && self.tags.is_empty()
+            && self.object_size_greater_than.is_none()
+            && self.object_size_less_than.is_none()
+    }
+
+    pub fn to_filter(&self) -> Result<LifecycleRuleFilter> {
+        if let (Some(min), Some(max)) = (self.object_size_greater_than, self.object_size_less_than)
+        {
+            if min >= max {
+                bail!(
+                    "object_size_greater_than ({}) must be less than object_size_less_than ({})",
+                    min,
+                    max
+                );
+            }
+        }
+
+        let mut tags = self
+            .tags
+            .iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let predicates = usize::from(self.prefix.is_some())
+            + tags.len()
+            + usize::from(self.object_size_greater_than.is_some())
+            + usize::from(self.object_size_less_than.is_some());
+
+        let filter = if predicates > 1 {
+            LifecycleRuleFilter::And(
+                LifecycleRuleAndOperator::builder()
+                    .set_prefix(self.prefix.clone())
+                    .set_tags(if tags.is_empty() { None } else { Some(tags) })
+                    .set_object_size_greater_than(self.object_size_greater_than)
+                    .set_object_size_less_than(self.object_size_less_than)
+                    .build(),
+            )
+        } else if let Some(tag) = tags.pop() {
+            LifecycleRuleFilter::Tag(tag)
+        } else if let Some(size) = self.object_size_greater_than {
+            LifecycleRuleFilter::ObjectSizeGreaterThan(size)
+        } else if let Some(size) = self.object_size_less_than {
+            LifecycleRuleFilter::ObjectSizeLessThan(size)
+        } else {
+            LifecycleRuleFilter::Prefix(self.prefix.clone().unwrap_or_default())
+        };
+
+        Ok(filter)
+    }
+}
+
+/// Fails on filter types the SDK doesn't know, rather than widening the rule to the
+/// whole bucket.
+impl TryFrom<&LifecycleRuleFilter> for FilterSpec {
+    type Error = anyhow::Error;
+
+    fn try_from(filter: &LifecycleRuleFilter) -> Result<Self> {
+        let tag_map = |tags: &[Tag]| {
+            tags.iter()
+                .map(|t| (t.key.clone(), t.value.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let spec = match filter {
+            LifecycleRuleFilter::Prefix(p) => FilterSpec {
+                prefix: Some(p.clone()).filter(|p| !p.is_empty()),
+                ..Default::default()
+            },
+            LifecycleRuleFilter::Tag(tag) => FilterSpec {
+                tags: tag_map(std::slice::from_ref(tag)),
+                ..Default::default()
+            },
+            LifecycleRuleFilter::ObjectSizeGreaterThan(size) => FilterSpec {
+                object_size_greater_than: Some(*size),
+                ..Default::default()
+            },
+            LifecycleRuleFilter::ObjectSizeLessThan(size) => FilterSpec {
+                object_size_less_than: Some(*size),
+                ..Default::default()
+            },
+            LifecycleRuleFilter::And(and) => FilterSpec {
+                prefix: and.prefix.clone().filter(|p| !p.is_empty()),
+                tags: tag_map(and.tags()),
+                object_size_greater_than: and.object_size_greater_than,
+                object_size_less_than: and.object_size_less_than,
+            },
+            other => bail!("unsupported lifecycle rule filter: {:?}", other),
+        };
+        Ok(spec)
+    }
+}
+
+impl TransitionSpec {
+    fn to_transition(&self) -> Result<Transition> {
+        let builder =
+            Transition::builder().storage_class(parse_storage_class(&self.storage_class)?);
+
+        match (self.days, &self.date) {
+            (Some(days), None) => Ok(builder.days(days).build()),
+            (None, Some(date)) => Ok(builder.date(parse_date(date)?).build()),
+            _ => bail!(
+                "transition to {} must set exactly one of 'days' or 'date'",
+                self.storage_class
+            ),
+        }
+    }
+}
+
+impl ExpirationSpec {
+    fn to_expiration(&self) -> Result<LifecycleExpiration> {
+        let date = self.date.as_deref().map(parse_date).transpose()?;
+
+        if self.days.is_some() && date.is_some() {
+            bail!("expiration cannot set both 'days' and 'date'");
+        }
+
+        // S3 rejects delete marker cleanup combined with a days or date expiration
+        if self.expired_object_delete_marker == Some(true)
+            && (self.days.is_some() || date.is_some())
+        {
+            bail!("expired_object_delete_marker cannot be combined with 'days' or 'date'");
+        }
+
+        Ok(LifecycleExpiration::builder()
+            .set_days(self.days)
+            .set_date(date)
+            .set_expired_object_delete_marker(self.expired_object_delete_marker)
+            .build())
+    }
+}
+
+impl fmt::Display for FilterSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+
+        if let Some(prefix) = &self.prefix {
+            parts.push(format!("prefix={}", prefix));
+        }
+        for (key, value) in &self.tags {
+            parts.push(format!("tag {}={}", key, value));
+        }
+        if let Some(size) = self.object_size_greater_than {
+            parts.push(format!("size>{}", size));
+        }
+        if let Some(size) = self.object_size_less_than {
+            parts.push(format!("size<{}", size));
+        }
+
+        if parts.is_empty() {
+            write!(f, "all objects")
+        } else {
+            write!(f, "{}", parts.join(" AND "))
+        }
+    }
+}
+
+impl fmt::Display for TransitionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.days, &self.date) {
+            (Some(days), _) => write!(f, "{}d → {}", days, self.storage_class),
+            (None, Some(date)) => write!(f, "{} → {}", date, self.storage_class),
+            (None, None) => write!(f, "? → {}", self.storage_class),
+        }
+    }
+}
+
+impl fmt::Display for ExpirationSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+
+        if let Some(days) = self.days {
+            parts.push(format!("{}d", days));
+        }
+        if let Some(date) = &self.date {
+            parts.push(date.clone());
+        }
+        if self.expired_object_delete_marker == Some(true) {
+            parts.push("expired delete markers".to_string());
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl fmt::Display for NoncurrentTransitionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}d noncurrent → {}",
+            self.noncurrent_days, self.storage_class
+        )?;
+        if let Some(versions) = self.newer_noncurrent_versions {
+            write!(f, " (keep {} newer)", versions)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for NoncurrentExpirationSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d noncurrent", self.noncurrent_days)?;
+        if let Some(versions) = self.newer_noncurrent_versions {
+            write!(f, " (keep {} newer)", versions)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AbortMultipartSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d after initiation", self.days_after_initiation)
+    }
+}
+
+fn storage_class_name(class: Option<&TransitionStorageClass>) -> String {
+    class.map(|c| c.as_str().to_string()).unwrap_or_default()
+}
+
+pub fn parse_storage_class(name: &str) -> Result<TransitionStorageClass> {
+    match name.to_uppercase().as_str() {
+        "STANDARD_IA" => Ok(TransitionStorageClass::StandardIa),
+        "ONEZONE_IA" => Ok(TransitionStorageClass::OnezoneIa),
+        "INTELLIGENT_TIERING" => Ok(TransitionStorageClass::IntelligentTiering),
+        "GLACIER_IR" => Ok(TransitionStorageClass::GlacierIr),
+        "GLACIER" => Ok(TransitionStorageClass::Glacier),
+        "DEEP_ARCHIVE" => Ok(TransitionStorageClass::DeepArchive),
+        _ => Err(anyhow!(
+            "Invalid storage class '{}'. Use: STANDARD_IA, ONEZONE_IA, INTELLIGENT_TIERING, GLACIER_IR, GLACIER or DEEP_ARCHIVE",
+            name
+        )),
+    }
+}
+
+/// Parses a lifecycle date. S3 only accepts midnight UTC, so a plain
+/// `YYYY-MM-DD` is the usual form; full RFC 3339 timestamps are passed through.
+pub fn parse_date(value: &str) -> Result<DateTime> {
+    let timestamp = if value.contains('T') {
+        value.to_string()
+    } else {
+        format!("{}T00:00:00Z", value)
+    };
+
+    DateTime::from_str(&timestamp, DateTimeFormat::DateTime)
+        .with_context(|| format!("Invalid date '{}' (expected YYYY-MM-DD)", value))
+}
+
+/// Formats a lifecycle date as `YYYY-MM-DD` when it falls on midnight UTC.
+pub fn format_date(date: &DateTime) -> String {
+    let timestamp = date
+        .fmt(DateTimeFormat::DateTime)
+        .unwrap_or_else(|_| date.secs().to_string());
+
+    match timestamp.strip_suffix("T00:00:00Z") {
+        Some(day) => day.to_string(),
+        None => timestamp,
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML_POLICY: &str = r#"
+rules:
+  - id: archive-logs
+    filter:
+      prefix: logs/
+    transitions:
+      - days: 30
+        storage_class: STANDARD_IA
+      - days: 90
+        storage_class: glacier
+    expiration:
+      days: 365
+  - id: cleanup
+    status: Disabled
+    filter:
+      tags:
+        retention: short
+      object_size_greater_than: 1024
+    abort_incomplete_multipart_upload:
+      days_after_initiation: 7
+  - id: legal-hold
+    transitions:
+      - date: 2027-01-01
+        storage_class: DEEP_ARCHIVE
+    expiration:
+      date: 2034-01-01
+"#;
+
+    #[test]
+    fn test_parse_yaml_policy() {
+        let policy = PolicyFormat::Yaml.parse(YAML_POLICY).unwrap();
+        assert_eq!(policy.rules.len(), 3);
+        assert_eq!(policy.rules[0].status, RuleStatus::Enabled);
+        assert_eq!(policy.rules[1].status, RuleStatus::Disabled);
+        assert_eq!(policy.rules[1].filter.tags["retention"], "short");
+    }
+
+    #[test]
+    fn test_policy_to_rules() {
+        let rules = PolicyFormat::Yaml
+            .parse(YAML_POLICY)
+            .unwrap()
+            .to_rules()
+            .unwrap();
+
+        assert_eq!(
+            rules[0].filter,
+            Some(LifecycleRuleFilter::Prefix("logs/".to_string()))
+        );
+        let transitions = rules[0].transitions.as_ref().unwrap();
+        assert_eq!(transitions[0].days, Some(30));
+        assert_eq!(
+            transitions[1].storage_class,
+            Some(TransitionStorageClass::Glacier)
+        );
+        assert_eq!(transitions[1].days, Some(90));
+
+        assert_eq!(rules[1].status, ExpirationStatus::Disabled);
+        assert!(matches!(rules[1].filter, Some(LifecycleRuleFilter::And(_))));
+        assert_eq!(
+            rules[1]
+                .abort_incomplete_multipart_upload
+                .as_ref()
+                .and_then(|a| a.days_after_initiation),
+            Some(7)
+        );
+
+        let transitions = rules[2].transitions.as_ref().unwrap();
+        assert_eq!(transitions[0].date, Some(parse_date("2027-01-01").unwrap()));
+        assert_eq!(
+            rules[2].expiration.as_ref().and_then(|e| e.date),
+            Some(parse_date("2034-01-01").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_json_and_toml_policy() {
+        let json = r#"{"rules": [{"id": "a", "expiration": {"days": 10}}]}"#;
+        let toml = "[[rules]]\nid = \"a\"\n\n[rules.expiration]\ndays = 10\n";
+
+        assert_eq!(
+            PolicyFormat::Json.parse(json).unwrap(),
+            PolicyFormat::Toml.parse(toml).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        let yaml = "rules:\n  - id: a\n    expiration_days: 10\n";
+        assert!(PolicyFormat::Yaml.parse(yaml).is_err());
+    }
+
+    #[test]
+    fn test_rule_round_trip() {
+        let policy = PolicyFormat::Yaml.parse(YAML_POLICY).unwrap();
+        let rules = policy.to_rules().unwrap();
+        let specs: Vec<RuleSpec> = rules
+            .iter()
+            .map(|r| RuleSpec::try_from(r).unwrap())
+            .collect();
+
+        assert_eq!(
+            specs,
+            policy.rules.iter().map(normalized).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let policy = LifecyclePolicy::from_rules(
+            &PolicyFormat::Yaml
+                .parse(YAML_POLICY)
+                .unwrap()
+                .to_rules()
+                .unwrap(),
+        )
+        .unwrap();
+
+        for format in [PolicyFormat::Yaml, PolicyFormat::Json, PolicyFormat::Toml] {
+            let rendered = format.render(&policy).unwrap();
+            assert_eq!(format.parse(&rendered).unwrap(), policy, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(
+            format_date(&parse_date("2027-01-01").unwrap()),
+            "2027-01-01"
+        );
+    }
+
+    fn normalized(spec: &RuleSpec) -> RuleSpec {
+        let mut spec = spec.clone();
+        for transition in &mut spec.transitions {
+            transition.storage_class = transition.storage_class.to_uppercase();
+        }
+        spec
+    }
+
+    #[test]
+    fn test_transition_requires_days_or_date() {
+        let spec = TransitionSpec {
+            days: None,
+            date: None,
+            storage_class: "GLACIER".to_string(),
+        };
+        assert!(spec.to_transition().is_err());
+    }
+
+    #[test]
+    fn test_filter_variants() {
+        let mut spec = FilterSpec {
+            tags: BTreeMap::from([("class".to_string(), "logs".to_string())]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            spec.to_filter().unwrap(),
+            LifecycleRuleFilter::Tag(_)
+        ));
+
+        spec.object_size_greater_than = Some(1024);
+        match spec.to_filter().unwrap() {
+            LifecycleRuleFilter::And(and) => {
+                assert_eq!(and.tags().len(), 1);
+                assert_eq!(and.object_size_greater_than, Some(1024));
+                assert_eq!(and.prefix, None);
+            }
+            other => panic!("expected And filter, got {:?}", other),
+        }
+
+        spec.object_size_less_than = Some(512);
+        assert!(spec.to_filter().is_err());
+    }
+
+    #[test]
+    fn test_cleanup_only_rule() {
+        let yaml = "rules:\n  - id: cleanup\n    expiration:\n      expired_object_delete_marker: true\n    abort_incomplete_multipart_upload:\n      days_after_initiation: 7\n";
+        let rules = PolicyFormat::Yaml.parse(yaml).unwrap().to_rules().unwrap();
+
+        assert!(rules[0].transitions.is_none());
+        assert_eq!(
+            rules[0]
+                .expiration
+                .as_ref()
+                .and_then(|e| e.expired_object_delete_marker),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_mixed_days_and_dates_rejected() {
+        let yaml = "rules:\n  - id: mixed\n    transitions:\n      - days: 30\n        storage_class: STANDARD_IA\n    expiration:\n      date: 2027-01-01\n";
+        assert!(PolicyFormat::Yaml.parse(yaml).unwrap().to_rules().is_err());
+    }
+
+    #[test]
+    fn test_rule_without_actions_rejected() {
+        let yaml = "rules:\n  - id: empty\n    filter:\n      prefix: logs/\n";
+        assert!(PolicyFormat::Yaml.parse(yaml).unwrap().to_rules().is_err());
+    }
+
+    #[test]
+    fn test_delete_marker_with_days_rejected() {
+        let spec = ExpirationSpec {
+            days: Some(30),
+            date: None,
+            expired_object_delete_marker: Some(true),
+        };
+        assert!(spec.to_expiration().is_err());
+    }
+
+    #[test]
+    fn test_invalid_storage_class() {
+        assert!(parse_storage_class("STANDARD").is_err());
+        assert_eq!(
+            parse_storage_class("deep_archive").unwrap(),
+            TransitionStorageClass::DeepArchive
+        );
+    }
+}
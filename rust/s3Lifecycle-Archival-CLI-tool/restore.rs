@@ -0,0 +1,423 @@
+// Restores objects from GLACIER and DEEP_ARCHIVE so they can be read again, and tracks
+// restores in progress.
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::{GlacierJobParameters, RestoreRequest, StorageClass, Tier};
+use clap::ValueEnum;
+
+use crate::retry::Retry;
+use crate::simulate::format_day;
+
+/// First wait between restore status checks; it doubles after each check.
+const INITIAL_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Retrieval tier: how quickly, and at what price, S3 makes a restored copy available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RestoreTier {
+    /// 1-5 minutes (GLACIER only)
+    Expedited,
+    /// 3-5 hours, or 12 hours from DEEP_ARCHIVE
+    Standard,
+    /// 5-12 hours, or 48 hours from DEEP_ARCHIVE
+    Bulk,
+}
+
+impl RestoreTier {
+    fn tier(self) -> Tier {
+        match self {
+            RestoreTier::Expedited => Tier::Expedited,
+            RestoreTier::Standard => Tier::Standard,
+            RestoreTier::Bulk => Tier::Bulk,
+        }
+    }
+}
+
+/// Objects to restore: a single key, or everything under a prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Key(String),
+    Prefix(String),
+}
+
+/// Whether objects in a storage class have to be restored before they can be read.
+/// GLACIER_IR objects are readable straight away.
+pub fn needs_restore(storage_class: &StorageClass) -> bool {
+    matches!(
+        storage_class,
+        StorageClass::Glacier | StorageClass::DeepArchive
+    )
+}
+
+/// DEEP_ARCHIVE has no expedited retrieval.
+pub fn tier_supported(storage_class: &StorageClass, tier: RestoreTier) -> bool {
+    !(tier == RestoreTier::Expedited && *storage_class == StorageClass::DeepArchive)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreStatus {
+    /// Readable without a restore
+    NotArchived,
+    NotRequested,
+    Pending,
+    /// Restored copy available until `expiry` (seconds since the Unix epoch)
+    Restored {
+        expiry: Option<i64>,
+    },
+}
+
+impl fmt::Display for RestoreStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreStatus::NotArchived => write!(f, "not archived"),
+            RestoreStatus::NotRequested => write!(f, "not requested"),
+            RestoreStatus::Pending => write!(f, "pending"),
+            RestoreStatus::Restored { expiry: Some(at) } => {
+                write!(f, "restored until {}", format_day(*at))
+            }
+            RestoreStatus::Restored { expiry: None } => write!(f, "restored"),
+        }
+    }
+}
+
+/// Parses the `x-amz-restore` header, e.g.
+/// `ongoing-request="false", expiry-date="Fri, 23 Dec 2012 00:00:00 GMT"`.
+pub fn parse_restore_header(header: &str) -> RestoreStatus {
+    if header.contains(r#"ongoing-request="true""#) {
+        return RestoreStatus::Pending;
+    }
+
+    let expiry = header
+        .split_once(r#"expiry-date=""#)
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .and_then(|(date, _)| DateTime::from_str(date, DateTimeFormat::HttpDate).ok())
+        .map(|date| date.secs());
+    RestoreStatus::Restored { expiry }
+}
+
+/// Waits twice as long after each check, up to `MAX_POLL_INTERVAL`.
+pub fn next_poll_interval(current: Duration) -> Duration {
+    (current * 2).min(MAX_POLL_INTERVAL)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    /// Keys with a restore started by this run or already in progress
+    pub requested: Vec<String>,
+    pub started: u64,
+    pub in_progress: u64,
+    pub not_archived: u64,
+    pub failed: u64,
+}
+
+/// Requests a restore of every archived object in `target`. Failed requests are counted in
+/// the summary rather than stopping the run, so the restores that did start can be waited on.
+pub async fn restore_objects(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    target: &Target,
+    days: i32,
+    tier: RestoreTier,
+) -> Result<RestoreSummary> {
+    let objects = list_targets(client, retry, bucket, target).await?;
+    let mut summary = RestoreSummary::default();
+
+    println!(
+        "Restoring archived objects for {} day(s) using the {:?} tier",
+        days, tier
+    );
+
+    for (key, storage_class) in objects {
+        if !needs_restore(&storage_class) {
+            println!("  - Skipped (not archived, {}): {}", storage_class, key);
+            summary.not_archived += 1;
+            continue;
+        }
+        if !tier_supported(&storage_class, tier) {
+            println!(
+                "  ✗ {:?} retrieval is not available for {}: {}",
+                tier, storage_class, key
+            );
+            summary.failed += 1;
+            continue;
+        }
+
+        let request = RestoreRequest::builder()
+            .days(days)
+            .glacier_job_parameters(GlacierJobParameters::builder().tier(tier.tier()).build()?)
+            .build();
+
+        let request = client
+            .restore_object()
+            .bucket(bucket)
+            .key(&key)
+            .restore_request(request);
+
+        match retry.send(|| request.clone().send()).await {
+            Ok(_) => {
+                println!("  ✓ Restore requested: {}", key);
+                summary.started += 1;
+                summary.requested.push(key);
+            }
+            Err(e) if e.code() == Some("RestoreAlreadyInProgress") => {
+                println!("  ⏳ Restore already in progress: {}", key);
+                summary.in_progress += 1;
+                summary.requested.push(key);
+            }
+            Err(e) => {
+                let error = anyhow::Error::new(e);
+                println!("  ✗ Failed to restore {}: {:#}", key, error);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "\n✓ Requested {} restore(s); {} already in progress",
+        summary.started, summary.in_progress
+    );
+    if summary.not_archived > 0 {
+        println!(
+            "  Skipped {} object(s) that are not archived",
+            summary.not_archived
+        );
+    }
+    if summary.failed > 0 {
+        println!("  ✗ {} restore request(s) failed", summary.failed);
+    }
+
+    Ok(summary)
+}
+
+async fn object_status(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    key: &str,
+) -> Result<RestoreStatus> {
+    let request = client.head_object().bucket(bucket).key(key);
+    let head = retry
+        .send(|| request.clone().send())
+        .await
+        .with_context(|| format!("Failed to read object metadata: {}", key))?;
+
+    let storage_class = head.storage_class.unwrap_or(StorageClass::Standard);
+    Ok(match head.restore.as_deref() {
+        _ if !needs_restore(&storage_class) => RestoreStatus::NotArchived,
+        Some(header) => parse_restore_header(header),
+        None => RestoreStatus::NotRequested,
+    })
+}
+
+/// Prints the restore status of every archived object selected by `target`.
+pub async fn print_restore_status(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    target: &Target,
+) -> Result<()> {
+    let objects = list_targets(client, retry, bucket, target).await?;
+    let (mut pending, mut restored, mut not_requested, mut not_archived) = (0, 0, 0, 0);
+
+    for (key, storage_class) in objects {
+        if !needs_restore(&storage_class) {
+            not_archived += 1;
+            continue;
+        }
+
+        let status = object_status(client, retry, bucket, &key).await?;
+        println!("  {:<30} {}", status.to_string(), key);
+        match status {
+            RestoreStatus::Pending => pending += 1,
+            RestoreStatus::Restored { .. } => restored += 1,
+            RestoreStatus::NotRequested => not_requested += 1,
+            RestoreStatus::NotArchived => not_archived += 1,
+        }
+    }
+
+    println!(
+        "\n{} restored, {} pending, {} not requested",
+        restored, pending, not_requested
+    );
+    if not_archived > 0 {
+        println!("  {} object(s) are not archived", not_archived);
+    }
+    Ok(())
+}
+
+/// Polls until every key has been restored, backing off between checks.
+pub async fn wait_for_restores(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    keys: &[String],
+) -> Result<()> {
+    let mut pending: Vec<&String> = keys.iter().collect();
+    let mut earliest_expiry: Option<i64> = None;
+    let mut interval = INITIAL_POLL_INTERVAL;
+
+    loop {
+        let mut still_pending = Vec::new();
+        for key in pending {
+            match object_status(client, retry, bucket, key).await? {
+                RestoreStatus::Pending => still_pending.push(key),
+                RestoreStatus::Restored { expiry } => {
+                    println!("  ✓ Restored: {}", key);
+                    if let Some(expiry) = expiry {
+                        earliest_expiry = Some(earliest_expiry.map_or(expiry, |e| e.min(expiry)));
+                    }
+                }
+                RestoreStatus::NotArchived => println!("  ✓ No longer archived: {}", key),
+                RestoreStatus::NotRequested => {
+                    bail!("Restore of {} is no longer in progress", key)
+                }
+            }
+        }
+        pending = still_pending;
+
+        if pending.is_empty() {
+            break;
+        }
+        println!(
+            "  {} of {} restore(s) pending; checking again in {}s",
+            pending.len(),
+            keys.len(),
+            interval.as_secs()
+        );
+        tokio::time::sleep(interval).await;
+        interval = next_poll_interval(interval);
+    }
+
+    match earliest_expiry {
+        Some(expiry) => println!(
+            "\n✓ All {} object(s) restored; available until at least {}",
+            keys.len(),
+            format_day(expiry)
+        ),
+        None => println!("\n✓ All {} object(s) restored", keys.len()),
+    }
+    Ok(())
+}
+
+/// Keys selected by `target`, with their storage classes.
+async fn list_targets(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    target: &Target,
+) -> Result<Vec<(String, StorageClass)>> {
+    match target {
+        Target::Key(key) => {
+            let request = client.head_object().bucket(bucket).key(key);
+            let head = retry
+                .send(|| request.clone().send())
+                .await
+                .with_context(|| format!("Failed to read object metadata: {}", key))?;
+            let storage_class = head.storage_class.unwrap_or(StorageClass::Standard);
+            Ok(vec![(key.clone(), storage_class)])
+        }
+        Target::Prefix(prefix) => {
+            let mut objects = Vec::new();
+            let mut continuation_token = None;
+
+            loop {
+                let request = client
+                    .list_objects_v2()
+                    .bucket(bucket)
+                    .prefix(prefix)
+                    .max_keys(1000)
+                    .set_continuation_token(continuation_token);
+                let page = retry
+                    .send(|| request.clone().send())
+                    .await
+                    .context("Failed to list objects")?;
+
+                for object in page.contents.unwrap_or_default() {
+                    let storage_class = object
+                        .storage_class
+                        .map_or(StorageClass::Standard, |class| {
+                            StorageClass::from(class.as_str())
+                        });
+                    if let Some(key) = object.key {
+                        objects.push((key, storage_class));
+                    }
+                }
+
+                continuation_token = page.next_continuation_token;
+                if continuation_token.is_none() {
+                    break;
+                }
+            }
+
+            Ok(objects)
+        }
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_restore() {
+        assert!(needs_restore(&StorageClass::Glacier));
+        assert!(needs_restore(&StorageClass::DeepArchive));
+        assert!(!needs_restore(&StorageClass::GlacierIr));
+        assert!(!needs_restore(&StorageClass::Standard));
+    }
+
+    #[test]
+    fn test_parse_restore_header() {
+        assert_eq!(
+            parse_restore_header(r#"ongoing-request="true""#),
+            RestoreStatus::Pending
+        );
+
+        let restored = parse_restore_header(
+            r#"ongoing-request="false", expiry-date="Fri, 23 Dec 2012 00:00:00 GMT""#,
+        );
+        assert_eq!(
+            restored,
+            RestoreStatus::Restored {
+                expiry: Some(1_356_220_800)
+            }
+        );
+        assert_eq!(restored.to_string(), "restored until 2012-12-23");
+    }
+
+    #[test]
+    fn test_poll_interval_backs_off() {
+        let mut interval = INITIAL_POLL_INTERVAL;
+        for _ in 0..10 {
+            interval = next_poll_interval(interval);
+        }
+        assert_eq!(
+            next_poll_interval(INITIAL_POLL_INTERVAL),
+            2 * INITIAL_POLL_INTERVAL
+        );
+        assert_eq!(interval, MAX_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_tier_supported() {
+        assert!(tier_supported(
+            &StorageClass::Glacier,
+            RestoreTier::Expedited
+        ));
+        assert!(!tier_supported(
+            &StorageClass::DeepArchive,
+            RestoreTier::Expedited
+        ));
+        assert!(tier_supported(
+            &StorageClass::DeepArchive,
+            RestoreTier::Bulk
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+// Retries and rate limiting for bulk S3 requests (archive and restore runs).
+//
+// The SDK's own retries give up quickly once S3 starts throttling a busy run, so bulk
+// commands use a client with SDK retries turned off and send each request through `Retry`.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aws_config::SdkConfig;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use rand::Rng;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+const BASE_DELAY: Duration = Duration::from_millis(200);
+const MAX_DELAY: Duration = Duration::from_secs(20);
+
+/// Error codes S3 returns when a request may succeed if sent again.
+const RETRYABLE_CODES: &[&str] = &[
+    "SlowDown",
+    "Throttling",
+    "ThrottlingException",
+    "RequestLimitExceeded",
+    "TooManyRequests",
+    "RequestTimeout",
+    "InternalError",
+    "ServiceUnavailable",
+];
+
+/// Client for requests sent through `Retry`, so SDK and `Retry` attempts don't multiply.
+pub fn client(config: &SdkConfig) -> aws_sdk_s3::Client {
+    let config = aws_sdk_s3::config::Builder::from(config)
+        .retry_config(RetryConfig::disabled())
+        .build();
+    aws_sdk_s3::Client::from_conf(config)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request, including the first
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            base_delay: BASE_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+
+    /// Longest wait before attempt `attempt + 1`: the base delay doubled for every attempt
+    /// so far, up to `max_delay`.
+    pub fn backoff_limit(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Waits as long as S3 asked for, up to `max_delay` so a bad `Retry-After` can't stall
+    /// the run, or a random time up to the backoff limit ("full jitter") so throttled tasks
+    /// don't all retry at once.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let limit = self.backoff_limit(attempt).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=limit))
+    }
+}
+
+/// Spaces requests out evenly to stay under a number of requests per second.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(1) / requests_per_second,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for the next free slot.
+    pub async fn acquire(&self) {
+        let at = {
+            let mut next = self.next.lock().await;
+            let at = (*next).max(Instant::now());
+            *next = at + self.interval;
+            at
+        };
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+/// Sends requests with a `RetryPolicy`, through an optional shared `RateLimiter`.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    policy: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Retry {
+    pub fn new(max_attempts: u32, max_rps: Option<u32>) -> Self {
+        Retry {
+            policy: RetryPolicy::new(max_attempts),
+            limiter: max_rps.map(|rps| Arc::new(RateLimiter::new(rps))),
+        }
+    }
+
+    /// Sends the request built by `request`, retrying throttling, server and network errors.
+    /// Every attempt, including retries, counts against the rate limit.
+    pub async fn send<T, E, F, Fut>(&self, mut request: F) -> Result<T, SdkError<E, HttpResponse>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SdkError<E, HttpResponse>>>,
+        E: ProvideErrorMetadata,
+    {
+        let mut attempt = 1;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+
+            match request().await {
+                Err(e) if attempt < self.policy.max_attempts && is_retryable(&e) => {
+                    tokio::time::sleep(self.policy.delay(attempt, retry_after(&e))).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_retryable<E: ProvideErrorMetadata>(error: &SdkError<E, HttpResponse>) -> bool {
+    match error {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(e) => {
+            is_retryable_response(e.err().code(), e.raw().status().as_u16())
+        }
+        _ => false,
+    }
+}
+
+/// Throttling and server errors are retried; other client errors are not.
+pub fn is_retryable_response(code: Option<&str>, status: u16) -> bool {
+    code.is_some_and(|code| RETRYABLE_CODES.contains(&code))
+        || status == 429
+        || (500..600).contains(&status) && status != 501
+}
+
+fn retry_after<E>(error: &SdkError<E, HttpResponse>) -> Option<Duration> {
+    let header = error.raw_response()?.headers().get("retry-after")?;
+    parse_retry_after(header, DateTime::from(std::time::SystemTime::now()).secs())
+}
+
+/// Parses a `Retry-After` header, given either as seconds or as an HTTP date.
+pub fn parse_retry_after(header: &str, now: i64) -> Option<Duration> {
+    let header = header.trim();
+    if let Ok(seconds) = header.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::from_str(header, DateTimeFormat::HttpDate).ok()?;
+    Some(Duration::from_secs((at.secs() - now).max(0) as u64))
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_to_limit() {
+        let policy = RetryPolicy::new(10);
+        assert_eq!(policy.backoff_limit(1), BASE_DELAY);
+        assert_eq!(policy.backoff_limit(3), BASE_DELAY * 4);
+        assert_eq!(policy.backoff_limit(30), MAX_DELAY);
+
+        for attempt in 1..10 {
+            assert!(policy.delay(attempt, None) <= policy.backoff_limit(attempt));
+        }
+        let asked = Duration::from_secs(5);
+        assert_eq!(policy.delay(1, Some(asked)), asked);
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(3600))), MAX_DELAY);
+    }
+
+    #[test]
+    fn test_is_retryable_response() {
+        assert!(is_retryable_response(Some("SlowDown"), 503));
+        assert!(is_retryable_response(None, 500));
+        assert!(is_retryable_response(None, 429));
+        assert!(!is_retryable_response(Some("AccessDenied"), 403));
+        // A wrong local clock stays wrong however long we wait
+        assert!(!is_retryable_response(Some("RequestTimeTooSkewed"), 403));
+        assert!(!is_retryable_response(Some("NotImplemented"), 501));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("5", 0), Some(Duration::from_secs(5)));
+        // Sun, 06 Nov 1994 08:49:37 GMT is 784111777
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", 784_111_767),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", 784_111_800),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_requests() {
+        let limiter = RateLimiter::new(20);
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire().await;
+        }
+        // The first request goes straight away, then one every 50ms
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
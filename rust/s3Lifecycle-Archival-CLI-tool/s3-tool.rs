@@ -6,15 +6,59 @@
 // tokio = { version = "1.35", features = ["full"] }
 // serde = { version = "1.0", features = ["derive"] }
 // serde_json = "1.0"
+// serde_yaml = "0.9"
+// toml = "0.8"
 // anyhow = "1.0"
+// globset = "0.4"
+// regex = "1.10"
+// rand = "0.8"
+
+mod archive;
+mod audit;
+mod buckets;
+mod cost;
+mod journal;
+mod output;
+mod plan;
+mod policy;
+mod restore;
+mod retry;
+mod simulate;
+mod stats;
+mod validate;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use anyhow::{Context, Result};
-use aws_sdk_s3::types::{
-    LifecycleConfiguration, LifecycleRule, LifecycleRuleFilter, ExpirationStatus,
-    Transition, TransitionStorageClass, LifecycleExpiration, NoncurrentVersionTransition,
-    NoncurrentVersionExpiration,
+use archive::{ArchiveOptions, ArchiveSettings, PreserveOptions};
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::{LifecycleConfiguration, LifecycleRule, LifecycleRuleFilter};
+use buckets::{BucketSelector, Buckets};
+use clap::{Args, Parser, Subcommand};
+use cost::{PricingTable, Projection};
+use globset::Glob;
+use journal::ResumeState;
+use output::OutputFormat;
+use plan::Plan;
+use policy::{
+    format_date, AbortMultipartSpec, ExpirationSpec, FilterSpec, LifecyclePolicy,
+    NoncurrentExpirationSpec, NoncurrentTransitionSpec, PolicyFormat, RuleSpec, RuleStatus,
+    TransitionSpec,
 };
-use clap::{Parser, Subcommand};
+use regex::Regex;
+use restore::{RestoreTier, Target};
+use retry::Retry;
+use simulate::TimelineEntry;
+use validate::{has_errors, storage_class_rank, validate_policy, validate_rules, Violation};
+
+/// Exit status when a run finished but some objects failed (see `archive --continue-on-error`).
+const EXIT_SOME_FAILED: i32 = 2;
+/// Exit status when `audit` finds a bucket that doesn't meet the required rules.
+const EXIT_NON_COMPLIANT: i32 = 3;
 
 #[derive(Parser)]
 #[command(name = "s3-lifecycle")]
@@ -22,51 +66,48 @@ use clap::{Parser, Subcommand};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Attempts per request for bulk runs (archive, restore, stats, simulate, cost), retrying
+    /// throttling and server errors
+    #[arg(long, global = true, default_value = "5", value_parser = clap::value_parser!(u32).range(1..))]
+    max_attempts: u32,
+    /// Output format for list, show, stats, audit and archive summaries (default: plain text)
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+    /// Limit bulk runs to this many S3 requests per second
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    max_rps: Option<u32>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// List all lifecycle rules for a bucket
     List {
-        /// S3 bucket name
-        #[arg(short, long)]
-        bucket: String,
+        #[command(flatten)]
+        buckets: BucketArgs,
     },
     /// Create a new lifecycle rule
     Create {
-        /// S3 bucket name
-        #[arg(short, long)]
-        bucket: String,
+        #[command(flatten)]
+        buckets: BucketArgs,
         /// Rule ID
         #[arg(short, long)]
         id: String,
-        /// Prefix filter (optional)
-        #[arg(short, long)]
-        prefix: Option<String>,
-        /// Days until transition to STANDARD_IA
-        #[arg(long)]
-        ia_days: Option<i32>,
-        /// Days until transition to GLACIER
+        #[command(flatten)]
+        rule: Box<RuleArgs>,
+        /// Print the plan without writing any changes
         #[arg(long)]
-        glacier_days: Option<i32>,
-        /// Days until transition to DEEP_ARCHIVE
-        #[arg(long)]
-        deep_archive_days: Option<i32>,
-        /// Days until expiration (deletion)
-        #[arg(long)]
-        expiration_days: Option<i32>,
-        /// Enable the rule
-        #[arg(long, default_value = "true")]
-        enabled: bool,
+        dry_run: bool,
     },
     /// Delete a lifecycle rule
     Delete {
-        /// S3 bucket name
-        #[arg(short, long)]
-        bucket: String,
+        #[command(flatten)]
+        buckets: BucketArgs,
         /// Rule ID to delete
         #[arg(short, long)]
         id: String,
+        /// Print the plan without writing any changes
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Show lifecycle rule details
     Show {
@@ -77,252 +118,978 @@ enum Commands {
         #[arg(short, long)]
         id: String,
     },
+    /// Replace all lifecycle rules with the rules from a policy file
+    Apply {
+        #[command(flatten)]
+        buckets: BucketArgs,
+        /// Policy file (.yaml, .json or .toml)
+        #[arg(short, long)]
+        file: PathBuf,
+        /// Print the plan without writing any changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the changes `apply` would make, without writing them
+    Plan {
+        /// S3 bucket name
+        #[arg(short, long)]
+        bucket: String,
+        /// Policy file (.yaml, .json or .toml)
+        #[arg(short, long)]
+        file: PathBuf,
+    },
+    /// Check a policy file against S3 lifecycle constraints without contacting AWS
+    Validate {
+        /// Policy file (.yaml, .json or .toml)
+        #[arg(short, long)]
+        file: PathBuf,
+    },
+    /// Export all lifecycle rules to a policy file that `apply` can read back
+    Export {
+        /// S3 bucket name
+        #[arg(short, long)]
+        bucket: String,
+        /// Output format (defaults to the file extension, or yaml)
+        #[arg(long, value_enum)]
+        format: Option<PolicyFormat>,
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    /// Predict which storage class each object will be in at a given date
+    Simulate {
+        /// S3 bucket name
+        #[arg(short, long)]
+        bucket: String,
+        /// Date to predict for (YYYY-MM-DD, defaults to today)
+        #[arg(long, value_parser = parse_lifecycle_date)]
+        at: Option<String>,
+        /// Simulate the rules in this policy file instead of the bucket's rules
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Add a proposed rule with this ID, built from the same flags as `create`
+        #[arg(long)]
+        id: Option<String>,
+        #[command(flatten)]
+        rule: Box<RuleArgs>,
+        /// Simulate disabled rules as if they were enabled
+        #[arg(long)]
+        include_disabled: bool,
+        /// Number of key path segments to group the timeline by
+        #[arg(long, default_value = "1")]
+        depth: usize,
+    },
+    /// Project monthly storage cost under the current rules and a proposed policy
+    Cost {
+        /// S3 bucket name
+        #[arg(short, long)]
+        bucket: String,
+        /// Proposed policy file to compare against the bucket's current rules
+        #[arg(long)]
+        policy: Option<PathBuf>,
+        /// Pricing table file (see pricing.toml)
+        #[arg(long, default_value = "pricing.toml")]
+        pricing: PathBuf,
+        /// Pricing region (defaults to the configured AWS region)
+        #[arg(long)]
+        region: Option<String>,
+        /// Number of months to project
+        #[arg(long, default_value = "12")]
+        months: u32,
+    },
+    /// Check buckets against a policy of lifecycle rules that must exist
+    Audit {
+        #[command(flatten)]
+        buckets: BucketArgs,
+        /// Required-rules file (.yaml, .json or .toml)
+        #[arg(long)]
+        policy: PathBuf,
+        /// JSON report to write
+        #[arg(long, default_value = "audit-report.json")]
+        report: PathBuf,
+    },
+    /// Break a bucket's storage down by prefix, storage class and object age
+    Stats {
+        /// S3 bucket name
+        #[arg(short, long)]
+        bucket: String,
+        /// Only count objects under this prefix
+        #[arg(short, long, default_value = "")]
+        prefix: String,
+        /// Number of key path segments to group by
+        #[arg(long, default_value = "1")]
+        depth: usize,
+    },
     /// Archive objects with a specific prefix immediately
     Archive {
+        /// S3 bucket name
+        #[arg(short, long, required_unless_present = "resume")]
+        bucket: Option<String>,
+        /// Prefix to archive
+        #[arg(short, long, required_unless_present = "resume")]
+        prefix: Option<String>,
+        /// Target storage class (GLACIER, DEEP_ARCHIVE, GLACIER_IR) [default: GLACIER]
+        #[arg(short, long)]
+        storage_class: Option<String>,
+        /// Number of objects to copy in parallel
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+        /// Part size for multipart copies of objects over 5GB (5MB to 5GB) [default: 512MB]
+        #[arg(long, value_parser = parse_part_size)]
+        part_size: Option<i64>,
+        #[command(flatten)]
+        filter: Box<ArchiveFilterArgs>,
+        #[command(flatten)]
+        preserve: Box<PreserveArgs>,
+        /// Checkpoint journal to write [default: archive-<bucket>-<timestamp>.jsonl]
+        #[arg(long, conflicts_with = "resume")]
+        journal: Option<PathBuf>,
+        /// Continue an interrupted run from its journal, with the options it was started with
+        #[arg(long, value_name = "JOURNAL")]
+        resume: Option<PathBuf>,
+        /// Record failed objects and carry on; exits with status 2 if any failed
+        #[arg(long)]
+        continue_on_error: bool,
+        /// Failure report to write (.csv for CSV, otherwise JSON) [default: <journal>.failures.json]
+        #[arg(long, requires = "continue_on_error")]
+        failure_report: Option<PathBuf>,
+        /// List matching objects and print totals without copying anything
+        #[arg(long, conflicts_with = "resume")]
+        dry_run: bool,
+        /// Pricing table file for the dry-run cost estimate (see pricing.toml)
+        #[arg(long, requires = "dry_run")]
+        pricing: Option<PathBuf>,
+        /// Pricing region (defaults to the configured AWS region)
+        #[arg(long, requires = "pricing")]
+        region: Option<String>,
+    },
+    /// Restore GLACIER or DEEP_ARCHIVE objects so they can be read
+    Restore {
         /// S3 bucket name
         #[arg(short, long)]
         bucket: String,
-        /// Prefix to archive
+        /// Object key to restore
+        #[arg(
+            short,
+            long,
+            required_unless_present = "prefix",
+            conflicts_with = "prefix"
+        )]
+        key: Option<String>,
+        /// Restore every archived object under this prefix
         #[arg(short, long)]
-        prefix: String,
-        /// Target storage class (GLACIER, DEEP_ARCHIVE, GLACIER_IR)
-        #[arg(short, long, default_value = "GLACIER")]
-        storage_class: String,
+        prefix: Option<String>,
+        /// Days to keep the restored copy available
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        days: i32,
+        /// Retrieval tier
+        #[arg(long, value_enum, ignore_case = true, default_value = "standard")]
+        tier: RestoreTier,
+        /// Wait until every restore has finished
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Show whether archived objects are restored, pending or not requested
+    RestoreStatus {
+        /// S3 bucket name
+        #[arg(short, long)]
+        bucket: String,
+        /// Object key to check
+        #[arg(
+            short,
+            long,
+            required_unless_present = "prefix",
+            conflicts_with = "prefix"
+        )]
+        key: Option<String>,
+        /// Check every archived object under this prefix
+        #[arg(short, long)]
+        prefix: Option<String>,
     },
 }
 
+/// The bucket to run on, or selectors that choose several buckets.
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+struct BucketArgs {
+    /// S3 bucket name
+    #[arg(short, long, conflicts_with_all = ["buckets_matching", "bucket_tag"])]
+    bucket: Option<String>,
+    /// Run on every bucket whose name matches this pattern (e.g. 'logs-*')
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    buckets_matching: Option<Glob>,
+    /// Run on every bucket with this tag (KEY=VALUE, repeatable; all must match)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    bucket_tag: Vec<(String, String)>,
+}
+
+impl BucketArgs {
+    fn to_buckets(&self) -> Buckets {
+        match &self.bucket {
+            Some(bucket) => Buckets::One(bucket.clone()),
+            None => Buckets::Selected(BucketSelector {
+                pattern: self.buckets_matching.as_ref().map(Glob::compile_matcher),
+                tags: self.bucket_tag.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Args)]
+struct RuleArgs {
+    /// Prefix filter (optional)
+    #[arg(short, long)]
+    prefix: Option<String>,
+    /// Tag filter as KEY=VALUE (repeatable)
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    tags: Vec<(String, String)>,
+    /// Only match objects larger than this size (bytes, or e.g. 128KB, 5GB)
+    #[arg(long, value_parser = parse_size)]
+    min_size: Option<i64>,
+    /// Only match objects smaller than this size (bytes, or e.g. 128KB, 5GB)
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<i64>,
+    /// Days until transition to STANDARD_IA
+    #[arg(long, conflicts_with = "ia_date")]
+    ia_days: Option<i32>,
+    /// Days until transition to GLACIER
+    #[arg(long, conflicts_with = "glacier_date")]
+    glacier_days: Option<i32>,
+    /// Days until transition to DEEP_ARCHIVE
+    #[arg(long, conflicts_with = "deep_archive_date")]
+    deep_archive_days: Option<i32>,
+    /// Days until expiration (deletion)
+    #[arg(long, conflicts_with = "expiration_date")]
+    expiration_days: Option<i32>,
+    /// Date of transition to STANDARD_IA (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_lifecycle_date)]
+    ia_date: Option<String>,
+    /// Date of transition to GLACIER (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_lifecycle_date)]
+    glacier_date: Option<String>,
+    /// Date of transition to DEEP_ARCHIVE (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_lifecycle_date)]
+    deep_archive_date: Option<String>,
+    /// Date of expiration (deletion) (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_lifecycle_date)]
+    expiration_date: Option<String>,
+    /// Days after becoming noncurrent until transition to STANDARD_IA
+    #[arg(long)]
+    noncurrent_ia_days: Option<i32>,
+    /// Days after becoming noncurrent until transition to GLACIER
+    #[arg(long)]
+    noncurrent_glacier_days: Option<i32>,
+    /// Days after becoming noncurrent until transition to DEEP_ARCHIVE
+    #[arg(long)]
+    noncurrent_deep_archive_days: Option<i32>,
+    /// Days after becoming noncurrent until expiration (deletion)
+    #[arg(long)]
+    noncurrent_expiration_days: Option<i32>,
+    /// Number of newer noncurrent versions to keep before noncurrent actions apply
+    #[arg(long)]
+    newer_noncurrent_versions: Option<i32>,
+    /// Days after initiation until incomplete multipart uploads are aborted
+    #[arg(long)]
+    abort_multipart_days: Option<i32>,
+    /// Remove delete markers that no longer have any noncurrent versions
+    #[arg(long)]
+    expire_delete_markers: bool,
+    /// Enable the rule
+    #[arg(long, default_value = "true")]
+    enabled: bool,
+}
+
+#[derive(Args)]
+struct ArchiveFilterArgs {
+    /// Only archive objects last modified at least this long ago (e.g. 180d, 12w, 36h)
+    #[arg(long, value_parser = parse_age)]
+    older_than: Option<i64>,
+    /// Only archive objects last modified less than this long ago
+    #[arg(long, value_parser = parse_age)]
+    newer_than: Option<i64>,
+    /// Only archive objects of at least this size (bytes, or e.g. 128KB, 5GB)
+    #[arg(long, value_parser = parse_size)]
+    min_size: Option<i64>,
+    /// Only archive objects of at most this size
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<i64>,
+    /// Only archive keys matching this glob (repeatable; `*` stops at `/`, `**` does not)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    include: Vec<Glob>,
+    /// Skip keys matching this glob (repeatable)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    exclude: Vec<Glob>,
+    /// Only archive keys matching this regular expression
+    #[arg(long)]
+    regex: Option<Regex>,
+    /// Only archive objects with this tag as KEY=VALUE (repeatable)
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    tags: Vec<(String, String)>,
+}
+
+#[derive(Args)]
+struct PreserveArgs {
+    /// Keep each object's server-side encryption settings, including its KMS key
+    #[arg(long, conflicts_with = "sse_kms_key_id")]
+    preserve_sse: bool,
+    /// Encrypt archived objects with this KMS key (key ID or ARN)
+    #[arg(long, value_name = "KEY")]
+    sse_kms_key_id: Option<String>,
+    /// Check that object tags carried over to the archived copy
+    #[arg(long)]
+    preserve_tags: bool,
+    /// Carry over object ACLs (copies are otherwise private)
+    #[arg(long)]
+    preserve_acl: bool,
+    /// Keep each object's checksum algorithm
+    #[arg(long)]
+    preserve_checksum: bool,
+    /// All of the --preserve-* options (SSE is left out with --sse-kms-key-id)
+    #[arg(long)]
+    preserve_all: bool,
+}
+
+impl PreserveArgs {
+    fn to_options(&self) -> PreserveOptions {
+        PreserveOptions {
+            sse: (self.preserve_sse || self.preserve_all) && self.sse_kms_key_id.is_none(),
+            sse_kms_key_id: self.sse_kms_key_id.clone(),
+            tags: self.preserve_tags || self.preserve_all,
+            acl: self.preserve_acl || self.preserve_all,
+            checksum: self.preserve_checksum || self.preserve_all,
+        }
+    }
+}
+
+impl ArchiveFilterArgs {
+    fn to_settings(
+        &self,
+        preserve: PreserveOptions,
+        part_size: i64,
+        continue_on_error: bool,
+    ) -> ArchiveSettings {
+        let patterns = |globs: &[Glob]| globs.iter().map(|g| g.glob().to_string()).collect();
+        ArchiveSettings {
+            older_than: self.older_than,
+            newer_than: self.newer_than,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            include: patterns(&self.include),
+            exclude: patterns(&self.exclude),
+            regex: self.regex.as_ref().map(|regex| regex.as_str().to_string()),
+            tags: self.tags.clone(),
+            preserve,
+            part_size,
+            continue_on_error,
+        }
+    }
+}
+
+impl RuleArgs {
+    fn to_spec(&self, id: &str) -> Result<RuleSpec> {
+        // A rule holds one value per tag key, so a repeated key would silently drop the others
+        let mut tags = BTreeMap::new();
+        for (key, value) in &self.tags {
+            if tags.insert(key.clone(), value.clone()).is_some() {
+                anyhow::bail!(
+                    "--tag {} is given more than once; a rule can match only one value per key",
+                    key
+                );
+            }
+        }
+
+        // Build transitions
+        let transitions = [
+            (self.ia_days, &self.ia_date, "STANDARD_IA"),
+            (self.glacier_days, &self.glacier_date, "GLACIER"),
+            (
+                self.deep_archive_days,
+                &self.deep_archive_date,
+                "DEEP_ARCHIVE",
+            ),
+        ]
+        .into_iter()
+        .filter(|(days, date, _)| days.is_some() || date.is_some())
+        .map(|(days, date, storage_class)| TransitionSpec {
+            days,
+            date: date.clone(),
+            storage_class: storage_class.to_string(),
+        })
+        .collect();
+
+        let noncurrent_version_transitions = [
+            (self.noncurrent_ia_days, "STANDARD_IA"),
+            (self.noncurrent_glacier_days, "GLACIER"),
+            (self.noncurrent_deep_archive_days, "DEEP_ARCHIVE"),
+        ]
+        .into_iter()
+        .filter_map(|(days, storage_class)| {
+            days.map(|days| NoncurrentTransitionSpec {
+                noncurrent_days: days,
+                storage_class: storage_class.to_string(),
+                newer_noncurrent_versions: self.newer_noncurrent_versions,
+            })
+        })
+        .collect();
+
+        Ok(RuleSpec {
+            id: id.to_string(),
+            status: if self.enabled {
+                RuleStatus::Enabled
+            } else {
+                RuleStatus::Disabled
+            },
+            filter: FilterSpec {
+                prefix: self.prefix.clone(),
+                tags,
+                object_size_greater_than: self.min_size,
+                object_size_less_than: self.max_size,
+            },
+            transitions,
+            expiration: match (
+                self.expiration_days,
+                &self.expiration_date,
+                self.expire_delete_markers,
+            ) {
+                (None, None, false) => None,
+                (days, date, delete_markers) => Some(ExpirationSpec {
+                    days,
+                    date: date.clone(),
+                    expired_object_delete_marker: delete_markers.then_some(true),
+                }),
+            },
+            noncurrent_version_transitions,
+            noncurrent_version_expiration: self.noncurrent_expiration_days.map(|days| {
+                NoncurrentExpirationSpec {
+                    noncurrent_days: days,
+                    newer_noncurrent_versions: self.newer_noncurrent_versions,
+                }
+            }),
+            abort_incomplete_multipart_upload: self.abort_multipart_days.map(|days| {
+                AbortMultipartSpec {
+                    days_after_initiation: days,
+                }
+            }),
+        })
+    }
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", value)),
+    }
+}
+
+fn parse_lifecycle_date(value: &str) -> Result<String, String> {
+    policy::parse_date(value)
+        .map(|_| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_size(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: i64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("invalid size unit in '{}'", value)),
+    };
+
+    number
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{}'", value))
+}
+
+/// Parses an age such as `180d`, `12w` or `36h` into seconds; a bare number is days.
+fn parse_age(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: i64 = match unit.trim().to_lowercase().as_str() {
+        "h" => 3_600,
+        "" | "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => return Err(format!("invalid age unit in '{}' (use h, d or w)", value)),
+    };
+
+    number
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid age '{}'", value))
+}
+
+fn parse_glob(value: &str) -> Result<Glob, String> {
+    archive::key_glob(value).map_err(|e| e.to_string())
+}
+
+fn parse_part_size(value: &str) -> Result<i64, String> {
+    let size = parse_size(value)?;
+    if (archive::MIN_PART_SIZE..=archive::MAX_PART_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!(
+            "part size must be between 5MB and 5GB, got '{}'",
+            value
+        ))
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 GB`.
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    
+
+    // Validation is purely local, so it runs without loading AWS configuration
+    if let Commands::Validate { file } = &cli.command {
+        return validate_policy_file(file);
+    }
+
     let config = aws_config::load_from_env().await;
     let client = aws_sdk_s3::Client::new(&config);
+    // Bulk commands send their requests through `retry`, which does its own retrying
+    let bulk_client = retry::client(&config);
+    let retry = Retry::new(cli.max_attempts, cli.max_rps);
 
     match cli.command {
-        Commands::List { bucket } => list_lifecycle_rules(&client, &bucket).await?,
+        Commands::List { buckets } => {
+            let output = cli.output.filter(|format| format.is_structured());
+            match (buckets.to_buckets(), output) {
+                // One document for all buckets, so the output stays parseable
+                (Buckets::Selected(selector), Some(format)) => {
+                    let mut policies = BTreeMap::new();
+                    for bucket in buckets::select(&client, &selector).await? {
+                        if let Some(e) = bucket.error {
+                            eprintln!("⚠ Skipping bucket '{}': {:#}", bucket.name, e);
+                            continue;
+                        }
+                        let rules = get_lifecycle_rules(&client, &bucket.name).await?;
+                        policies.insert(bucket.name, LifecyclePolicy::from_rules(&rules)?);
+                    }
+                    print!("{}", format.render(&policies)?);
+                }
+                (buckets, _) => {
+                    buckets::for_each(&client, &buckets, |bucket| {
+                        let client = &client;
+                        async move {
+                            let count = list_lifecycle_rules(client, &bucket, cli.output).await?;
+                            Ok(format!("{} rule(s)", count))
+                        }
+                    })
+                    .await?
+                }
+            }
+        }
         Commands::Create {
+            buckets,
+            id,
+            rule,
+            dry_run,
+        } => {
+            // Reject invalid flags once, rather than once for every bucket
+            rule.to_spec(&id)?.to_rule()?;
+            buckets::for_each(&client, &buckets.to_buckets(), |bucket| {
+                let (client, id, rule) = (&client, &id, &rule);
+                async move {
+                    let outcome = create_lifecycle_rule(client, &bucket, id, rule, dry_run).await?;
+                    Ok(outcome.to_string())
+                }
+            })
+            .await?
+        }
+        Commands::Delete {
+            buckets,
+            id,
+            dry_run,
+        } => {
+            buckets::for_each(&client, &buckets.to_buckets(), |bucket| {
+                let (client, id) = (&client, &id);
+                async move {
+                    let outcome = delete_lifecycle_rule(client, &bucket, id, dry_run).await?;
+                    Ok(outcome.to_string())
+                }
+            })
+            .await?
+        }
+        Commands::Show { bucket, id } => {
+            show_lifecycle_rule(&client, &bucket, &id, cli.output).await?
+        }
+        Commands::Apply {
+            buckets,
+            file,
+            dry_run,
+        } => {
+            buckets::for_each(&client, &buckets.to_buckets(), |bucket| {
+                let (client, file) = (&client, &file);
+                async move {
+                    let outcome = apply_lifecycle_policy(client, &bucket, file, dry_run).await?;
+                    Ok(outcome.to_string())
+                }
+            })
+            .await?
+        }
+        Commands::Plan { bucket, file } => {
+            apply_lifecycle_policy(&client, &bucket, &file, true).await?;
+        }
+        Commands::Validate { .. } => unreachable!("handled before creating the client"),
+        Commands::Export {
+            bucket,
+            format,
+            file,
+        } => export_lifecycle_policy(&client, &bucket, format, file.as_deref()).await?,
+        Commands::Simulate {
             bucket,
+            at,
+            file,
             id,
-            prefix,
-            ia_days,
-            glacier_days,
-            deep_archive_days,
-            expiration_days,
-            enabled,
+            rule,
+            include_disabled,
+            depth,
         } => {
-            create_lifecycle_rule(
+            simulate_lifecycle(
                 &client,
+                &bulk_client,
+                &retry,
                 &bucket,
-                &id,
-                prefix.as_deref(),
-                ia_days,
-                glacier_days,
-                deep_archive_days,
-                expiration_days,
-                enabled,
+                at.as_deref(),
+                file.as_deref(),
+                id.as_deref(),
+                &rule,
+                include_disabled,
+                depth,
             )
             .await?
         }
-        Commands::Delete { bucket, id } => delete_lifecycle_rule(&client, &bucket, &id).await?,
-        Commands::Show { bucket, id } => show_lifecycle_rule(&client, &bucket, &id).await?,
+        Commands::Cost {
+            bucket,
+            policy,
+            pricing,
+            region,
+            months,
+        } => {
+            let region = pricing_region(region, &config);
+            estimate_cost(
+                &client,
+                &bulk_client,
+                &retry,
+                &bucket,
+                policy.as_deref(),
+                &pricing,
+                &region,
+                months,
+            )
+            .await?
+        }
+        Commands::Audit {
+            buckets,
+            policy,
+            report,
+        } => {
+            let policy = audit::RequiredPolicy::load(&policy)?;
+            let selected = buckets::resolve(&client, &buckets.to_buckets()).await?;
+            let audit_report = audit::audit_buckets(&client, selected, &policy).await;
+            audit::write_report(&report, &audit_report)?;
+
+            match cli.output.filter(|format| format.is_structured()) {
+                Some(format) => print!("{}", format.render(&audit_report)?),
+                None => {
+                    audit::print_report(&audit_report);
+                    println!("Report written to {}", report.display());
+                }
+            }
+            if !audit_report.compliant {
+                std::process::exit(EXIT_NON_COMPLIANT);
+            }
+        }
+        Commands::Stats {
+            bucket,
+            prefix,
+            depth,
+        } => {
+            let stats = stats::collect(&bulk_client, &retry, &bucket, &prefix, depth).await?;
+            stats::print_stats(&stats, &bucket, depth, cli.output)?
+        }
         Commands::Archive {
             bucket,
             prefix,
             storage_class,
-        } => archive_objects(&client, &bucket, &prefix, &storage_class).await?,
-    }
-
-    Ok(())
-}
-
-async fn list_lifecycle_rules(client: &aws_sdk_s3::Client, bucket: &str) -> Result<()> {
-    println!("Fetching lifecycle rules for bucket: {}", bucket);
-    
-    match client
-        .get_bucket_lifecycle_configuration()
-        .bucket(bucket)
-        .send()
-        .await
-    {
-        Ok(output) => {
-            if let Some(rules) = output.rules {
-                if rules.is_empty() {
-                    println!("No lifecycle rules found.");
-                } else {
-                    println!("\nLifecycle Rules:");
-                    println!("{:-<80}", "");
-                    for rule in rules {
-                        print_rule(&rule);
+            concurrency,
+            part_size,
+            filter,
+            preserve,
+            journal,
+            resume,
+            continue_on_error,
+            failure_report,
+            dry_run,
+            pricing,
+            region,
+        } => {
+            let given_settings =
+                |part_size| filter.to_settings(preserve.to_options(), part_size, continue_on_error);
+            let resume = resume
+                .map(|path| ResumeState::load(&path).map(|s| (path, s)))
+                .transpose()?;
+            let (bucket, prefix, storage_class, journal, settings, resume) = match resume {
+                Some((_, state)) if state.finished => {
+                    println!("The archive run in this journal has already finished.");
+                    return Ok(());
+                }
+                Some((path, state)) => {
+                    let given = [
+                        ("bucket", bucket, &state.bucket),
+                        ("prefix", prefix, &state.prefix),
+                        ("storage-class", storage_class, &state.storage_class),
+                    ];
+                    for (name, value, recorded) in given {
+                        if value.is_some_and(|value| !value.eq_ignore_ascii_case(recorded)) {
+                            anyhow::bail!(
+                                "--{} does not match the journal, which archives '{}'",
+                                name,
+                                recorded
+                            );
+                        }
+                    }
+                    // Resuming with other filters would archive objects the run left out
+                    let given = given_settings(part_size.unwrap_or(state.settings.part_size));
+                    if let Some(flag) = given.conflicting_flag(&state.settings) {
+                        anyhow::bail!(
+                            "--{} does not match the journal; leave it out to resume with the \
+                             recorded options",
+                            flag
+                        );
                     }
+                    let (bucket, prefix, storage_class, settings) = (
+                        state.bucket.clone(),
+                        state.prefix.clone(),
+                        state.storage_class.clone(),
+                        state.settings.clone(),
+                    );
+                    (bucket, prefix, storage_class, path, settings, Some(state))
                 }
-            } else {
-                println!("No lifecycle rules found.");
+                None => {
+                    let bucket = bucket.context("--bucket is required")?;
+                    let journal = journal.unwrap_or_else(|| {
+                        let now = DateTime::from(SystemTime::now()).secs();
+                        PathBuf::from(format!("archive-{}-{}.jsonl", bucket, now))
+                    });
+                    let prefix = prefix.context("--prefix is required")?;
+                    let storage_class = storage_class.unwrap_or_else(|| "GLACIER".to_string());
+                    let settings = given_settings(part_size.unwrap_or(archive::DEFAULT_PART_SIZE));
+                    (bucket, prefix, storage_class, journal, settings, None)
+                }
+            };
+
+            let failure_report =
+                failure_report.unwrap_or_else(|| journal.with_extension("failures.json"));
+            let options = ArchiveOptions {
+                concurrency: usize::from(concurrency),
+                filter: settings.filter()?,
+                settings,
+                journal,
+                resume,
+                failure_report,
+                retry,
+                output: cli.output,
+                dry_run,
+                pricing: match pricing {
+                    Some(path) => {
+                        let region = pricing_region(region, &config);
+                        Some(PricingTable::load(&path)?.region(&region)?.clone())
+                    }
+                    None => None,
+                },
+            };
+            let progress =
+                archive::archive_objects(&bulk_client, &bucket, &prefix, &storage_class, &options)
+                    .await?;
+            if !progress.failures.is_empty() {
+                std::process::exit(EXIT_SOME_FAILED);
             }
         }
-        Err(e) => {
-            if e.to_string().contains("NoSuchLifecycleConfiguration") {
-                println!("No lifecycle configuration found for this bucket.");
-            } else {
-                return Err(e.into());
+        Commands::Restore {
+            bucket,
+            key,
+            prefix,
+            days,
+            tier,
+            wait,
+        } => {
+            let target = restore_target(key, prefix)?;
+            let summary =
+                restore::restore_objects(&bulk_client, &retry, &bucket, &target, days, tier)
+                    .await?;
+            if wait && !summary.requested.is_empty() {
+                println!("\nWaiting for restores to finish...");
+                restore::wait_for_restores(&bulk_client, &retry, &bucket, &summary.requested)
+                    .await?;
+            }
+            if summary.failed > 0 {
+                anyhow::bail!("{} restore request(s) failed", summary.failed);
             }
         }
+        Commands::RestoreStatus {
+            bucket,
+            key,
+            prefix,
+        } => {
+            let target = restore_target(key, prefix)?;
+            restore::print_restore_status(&bulk_client, &retry, &bucket, &target).await?
+        }
     }
-    
+
     Ok(())
 }
 
-async fn create_lifecycle_rule(
-    client: &aws_sdk_s3::Client,
-    bucket: &str,
-    id: &str,
-    prefix: Option<&str>,
-    ia_days: Option<i32>,
-    glacier_days: Option<i32>,
-    deep_archive_days: Option<i32>,
-    expiration_days: Option<i32>,
-    enabled: bool,
-) -> Result<()> {
-    // Get existing rules
-    let mut existing_rules = match client
-        .get_bucket_lifecycle_configuration()
-        .bucket(bucket)
-        .send()
-        .await
-    {
-        Ok(output) => output.rules.unwrap_or_default(),
-        Err(_) => vec![],
-    };
-
-    // Build transitions
-    let mut transitions = Vec::new();
-    
-    if let Some(days) = ia_days {
-        transitions.push(
-            Transition::builder()
-                .days(days)
-                .storage_class(TransitionStorageClass::StandardIa)
-                .build(),
-        );
+fn restore_target(key: Option<String>, prefix: Option<String>) -> Result<Target> {
+    match key {
+        Some(key) => Ok(Target::Key(key)),
+        None => Ok(Target::Prefix(
+            prefix.context("--key or --prefix is required")?,
+        )),
     }
-    
-    if let Some(days) = glacier_days {
-        transitions.push(
-            Transition::builder()
-                .days(days)
-                .storage_class(TransitionStorageClass::Glacier)
-                .build(),
-        );
-    }
-    
-    if let Some(days) = deep_archive_days {
-        transitions.push(
-            Transition::builder()
-                .days(days)
-                .storage_class(TransitionStorageClass::DeepArchive)
-                .build(),
-        );
-    }
-
-    // Build filter
-    let filter = if let Some(p) = prefix {
-        LifecycleRuleFilter::Prefix(p.to_string())
-    } else {
-        LifecycleRuleFilter::Prefix(String::new())
-    };
+}
 
-    // Build rule
-    let mut rule_builder = LifecycleRule::builder()
-        .id(id)
-        .filter(filter)
-        .status(if enabled {
-            ExpirationStatus::Enabled
-        } else {
-            ExpirationStatus::Disabled
-        });
+/// The region to look prices up for: the one given, else the configured AWS region.
+fn pricing_region(region: Option<String>, config: &aws_config::SdkConfig) -> String {
+    region
+        .or_else(|| config.region().map(|r| r.to_string()))
+        .unwrap_or_else(|| "us-east-1".to_string())
+}
 
-    for transition in transitions {
-        rule_builder = rule_builder.transitions(transition);
+async fn list_lifecycle_rules(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    output: Option<OutputFormat>,
+) -> Result<usize> {
+    if let Some(format) = output {
+        let policy = LifecyclePolicy::from_rules(&get_lifecycle_rules(client, bucket).await?)?;
+        print_rules(&policy, format)?;
+        return Ok(policy.rules.len());
     }
 
-    if let Some(days) = expiration_days {
-        rule_builder = rule_builder.expiration(
-            LifecycleExpiration::builder().days(days).build(),
-        );
+    println!("Fetching lifecycle rules for bucket: {}", bucket);
+    
+    let rules = get_lifecycle_rules(client, bucket).await?;
+    if rules.is_empty() {
+        println!("No lifecycle rules found.");
+    } else {
+        println!("\nLifecycle Rules:");
+        println!("{:-<80}", "");
+        for rule in &rules {
+            print_rule(rule);
+        }
     }
 
-    let new_rule = rule_builder.build()?;
+    Ok(rules.len())
+}
 
-    // Remove existing rule with same ID if it exists
-    existing_rules.retain(|r| r.id.as_deref() != Some(id));
-    existing_rules.push(new_rule);
+async fn create_lifecycle_rule(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    id: &str,
+    rule: &RuleArgs,
+    dry_run: bool,
+) -> Result<WriteOutcome> {
+    // Get existing rules
+    let existing_rules = get_lifecycle_rules(client, bucket).await?;
 
-    // Apply configuration
-    let lifecycle_config = LifecycleConfiguration::builder()
-        .set_rules(Some(existing_rules))
-        .build()?;
+    // Build rule
+    let new_rule = rule.to_spec(id)?.to_rule()?;
 
-    client
-        .put_bucket_lifecycle_configuration()
-        .bucket(bucket)
-        .lifecycle_configuration(lifecycle_config)
-        .send()
-        .await
-        .context("Failed to create lifecycle rule")?;
+    // Replace existing rule with same ID if it exists
+    let mut rules = existing_rules.clone();
+    rules.retain(|r| r.id.as_deref() != Some(id));
+    rules.push(new_rule);
 
-    println!("✓ Lifecycle rule '{}' created successfully for bucket '{}'", id, bucket);
+    let outcome = write_lifecycle_rules(client, bucket, &existing_rules, rules, dry_run).await?;
+    if outcome == WriteOutcome::Written {
+        println!("✓ Lifecycle rule '{}' created successfully for bucket '{}'", id, bucket);
+    }
     
-    Ok(())
+    Ok(outcome)
 }
 
-async fn delete_lifecycle_rule(client: &aws_sdk_s3::Client, bucket: &str, id: &str) -> Result<()> {
+async fn delete_lifecycle_rule(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    id: &str,
+    dry_run: bool,
+) -> Result<WriteOutcome> {
     // Get existing rules
-    let output = client
-        .get_bucket_lifecycle_configuration()
-        .bucket(bucket)
-        .send()
-        .await
-        .context("Failed to get lifecycle configuration")?;
+    let existing_rules = get_lifecycle_rules(client, bucket).await?;
 
-    let mut rules = output.rules.unwrap_or_default();
-    
-    let original_len = rules.len();
+    let mut rules = existing_rules.clone();
     rules.retain(|r| r.id.as_deref() != Some(id));
 
-    if rules.len() == original_len {
+    if rules.len() == existing_rules.len() {
         println!("Rule '{}' not found.", id);
-        return Ok(());
+        return Ok(WriteOutcome::Unchanged);
     }
 
-    if rules.is_empty() {
-        // Delete entire lifecycle configuration if no rules left
-        client
-            .delete_bucket_lifecycle()
-            .bucket(bucket)
-            .send()
-            .await
-            .context("Failed to delete lifecycle configuration")?;
-        println!("✓ All lifecycle rules deleted from bucket '{}'", bucket);
-    } else {
-        // Update with remaining rules
-        let lifecycle_config = LifecycleConfiguration::builder()
-            .set_rules(Some(rules))
-            .build()?;
-
-        client
-            .put_bucket_lifecycle_configuration()
-            .bucket(bucket)
-            .lifecycle_configuration(lifecycle_config)
-            .send()
-            .await
-            .context("Failed to update lifecycle configuration")?;
-        
-        println!("✓ Lifecycle rule '{}' deleted from bucket '{}'", id, bucket);
+    let all_deleted = rules.is_empty();
+    let outcome = write_lifecycle_rules(client, bucket, &existing_rules, rules, dry_run).await?;
+    if outcome == WriteOutcome::Written {
+        if all_deleted {
+            println!("✓ All lifecycle rules deleted from bucket '{}'", bucket);
+        } else {
+            println!("✓ Lifecycle rule '{}' deleted from bucket '{}'", id, bucket);
+        }
     }
 
-    Ok(())
+    Ok(outcome)
 }
 
-async fn show_lifecycle_rule(client: &aws_sdk_s3::Client, bucket: &str, id: &str) -> Result<()> {
+async fn show_lifecycle_rule(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    id: &str,
+    output: Option<OutputFormat>,
+) -> Result<()> {
+    if let Some(format) = output {
+        let rules = get_lifecycle_rules(client, bucket).await?;
+        let rule = rules
+            .iter()
+            .find(|r| r.id.as_deref() == Some(id))
+            .with_context(|| format!("Rule '{}' not found", id))?;
+        let rule = RuleSpec::try_from(rule)?;
+        match format {
+            OutputFormat::Table => print!("{}", output::rules_table(&[rule])),
+            _ => print!("{}", format.render(&rule)?),
+        }
+        return Ok(());
+    }
+
     let output = client
         .get_bucket_lifecycle_configuration()
         .bucket(bucket)
@@ -345,73 +1112,452 @@ async fn show_lifecycle_rule(client: &aws_sdk_s3::Client, bucket: &str, id: &str
     Ok(())
 }
 
-async fn archive_objects(
+async fn apply_lifecycle_policy(
     client: &aws_sdk_s3::Client,
     bucket: &str,
-    prefix: &str,
-    storage_class: &str,
+    file: &Path,
+    dry_run: bool,
+) -> Result<WriteOutcome> {
+    let policy = LifecyclePolicy::load(file)?;
+    let rules = policy.to_rules()?;
+    let rule_count = rules.len();
+
+    let existing_rules = get_lifecycle_rules(client, bucket).await?;
+
+    let outcome = write_lifecycle_rules(client, bucket, &existing_rules, rules, dry_run).await?;
+    if outcome == WriteOutcome::Written {
+        println!(
+            "✓ Applied {} lifecycle rule(s) from '{}' to bucket '{}'",
+            rule_count,
+            file.display(),
+            bucket
+        );
+    }
+
+    Ok(outcome)
+}
+
+fn validate_policy_file(file: &Path) -> Result<()> {
+    let policy = LifecyclePolicy::load(file)?;
+
+    if !print_violations(&validate_policy(&policy)) {
+        anyhow::bail!("Policy file '{}' failed validation", file.display());
+    }
+
+    println!(
+        "✓ Policy file '{}' is valid ({} rule(s))",
+        file.display(),
+        policy.rules.len()
+    );
+    Ok(())
+}
+
+/// Prints every violation found in `rules` and returns whether S3 would accept them.
+fn check_lifecycle_rules(rules: &[LifecycleRule]) -> bool {
+    print_violations(&validate_rules(rules))
+}
+
+/// Prints `violations` and returns whether S3 would accept the rules they were found in.
+fn print_violations(violations: &[Violation]) -> bool {
+    for violation in violations {
+        println!("{}", violation);
+    }
+    if !violations.is_empty() {
+        println!();
+    }
+    !has_errors(violations)
+}
+
+async fn export_lifecycle_policy(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    format: Option<PolicyFormat>,
+    file: Option<&Path>,
 ) -> Result<()> {
-    println!("Archiving objects with prefix '{}' to {}", prefix, storage_class);
+    let rules = get_lifecycle_rules(client, bucket).await?;
+    let policy = LifecyclePolicy::from_rules(&rules)?;
+
+    let format = match (format, file) {
+        (Some(format), _) => format,
+        (None, Some(path)) => PolicyFormat::from_path(path)?,
+        (None, None) => PolicyFormat::Yaml,
+    };
+    let contents = format.render(&policy)?;
 
-    let storage_class_enum = match storage_class.to_uppercase().as_str() {
-        "GLACIER" => TransitionStorageClass::Glacier,
-        "DEEP_ARCHIVE" => TransitionStorageClass::DeepArchive,
-        "GLACIER_IR" => TransitionStorageClass::GlacierIr,
-        _ => {
-            println!("Invalid storage class. Use: GLACIER, DEEP_ARCHIVE, or GLACIER_IR");
-            return Ok(());
+    match file {
+        Some(path) => {
+            fs::write(path, contents)
+                .with_context(|| format!("Failed to write policy file: {}", path.display()))?;
+            println!(
+                "✓ Exported {} lifecycle rule(s) from bucket '{}' to '{}'",
+                policy.rules.len(),
+                bucket,
+                path.display()
+            );
         }
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn simulate_lifecycle(
+    client: &aws_sdk_s3::Client,
+    bulk_client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    at: Option<&str>,
+    file: Option<&Path>,
+    id: Option<&str>,
+    rule: &RuleArgs,
+    include_disabled: bool,
+    depth: usize,
+) -> Result<()> {
+    let mut rules: Vec<RuleSpec> = match file {
+        Some(path) => {
+            let policy = LifecyclePolicy::load(path)?;
+            policy.to_rules()?;
+            policy.rules
+        }
+        None => get_lifecycle_rules(client, bucket)
+            .await?
+            .iter()
+            .map(RuleSpec::try_from)
+            .collect::<Result<_>>()?,
     };
 
-    // List objects with prefix
-    let mut continuation_token = None;
-    let mut total_objects = 0;
+    // Overlay the proposed rule the same way `create` would
+    match id {
+        Some(id) => {
+            let proposed = rule.to_spec(id)?;
+            proposed.to_rule()?;
+            rules.retain(|r| r.id != id);
+            rules.push(proposed);
+        }
+        None if rule.to_spec("")?.has_actions() => {
+            anyhow::bail!("--id is required to simulate a proposed rule")
+        }
+        None => {}
+    }
+    rules.retain(|r| include_disabled || r.status == RuleStatus::Enabled);
 
-    loop {
-        let mut list_req = client
-            .list_objects_v2()
-            .bucket(bucket)
-            .prefix(prefix)
-            .max_keys(1000);
+    let now = DateTime::from(SystemTime::now()).secs();
+    let at_secs = match at {
+        Some(date) => policy::parse_date(date)?.secs(),
+        None => now,
+    };
+    let at_day = simulate::format_day(at_secs);
+
+    println!(
+        "Simulating {} lifecycle rule(s) on bucket '{}' as of {}\n",
+        rules.len(),
+        bucket,
+        at_day
+    );
+
+    let objects = simulate::list_objects(bulk_client, retry, bucket, &rules, now).await?;
+    if objects.is_empty() {
+        println!("No objects found in bucket '{}'", bucket);
+        return Ok(());
+    }
+
+    let predictions: Vec<_> = objects
+        .iter()
+        .map(|object| simulate::predict(&rules, object))
+        .collect();
+
+    println!(
+        "{:<10}  {:>10}  {:<19}  {:<19}  {:<20}  KEY",
+        "MODIFIED",
+        "SIZE",
+        "NOW",
+        format!("AT {}", at_day),
+        "RULES"
+    );
+    for (object, prediction) in objects.iter().zip(&predictions) {
+        let rule_ids = if prediction.rule_ids.is_empty() {
+            "-".to_string()
+        } else {
+            prediction.rule_ids.join(",")
+        };
+        println!(
+            "{:<10}  {:>10}  {:<19}  {:<19}  {:<20}  {}",
+            simulate::format_day(object.last_modified),
+            format_bytes(object.size),
+            object.storage_class,
+            prediction
+                .storage_class_at(object, at_secs)
+                .unwrap_or_else(|| "EXPIRED".to_string()),
+            rule_ids,
+            object.key
+        );
+    }
+
+    let mut summary: Vec<_> = simulate::class_summary(&objects, &predictions, at_secs)
+        .into_iter()
+        .collect();
+    summary.sort_by_key(|(class, _)| storage_class_rank(class).unwrap_or(u8::MAX));
+
+    println!("\nStorage on {}:", at_day);
+    for (class, TimelineEntry { objects, bytes }) in summary {
+        println!(
+            "  {:<19} {:>8} object(s)  {:>10}",
+            class,
+            objects,
+            format_bytes(bytes)
+        );
+    }
+
+    let until = at.map(|_| at_secs);
+    let timeline = simulate::timeline(&objects, &predictions, now, until, depth);
+
+    println!("\nUpcoming lifecycle actions:");
+    if timeline.is_empty() {
+        println!("  (none)");
+    }
+    for ((day, prefix, action), TimelineEntry { objects, bytes }) in timeline {
+        let prefix = if prefix.is_empty() { "(root)" } else { &prefix };
+        println!(
+            "  {}  {:<30} {:<22} {:>8} object(s)  {:>10}",
+            simulate::format_day(day),
+            prefix,
+            action,
+            objects,
+            format_bytes(bytes)
+        );
+    }
+
+    Ok(())
+}
 
-        if let Some(token) = continuation_token {
-            list_req = list_req.continuation_token(token);
+#[allow(clippy::too_many_arguments)]
+async fn estimate_cost(
+    client: &aws_sdk_s3::Client,
+    bulk_client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    policy: Option<&Path>,
+    pricing: &Path,
+    region: &str,
+    months: u32,
+) -> Result<()> {
+    let pricing = PricingTable::load(pricing)?;
+    let pricing = pricing.region(region)?;
+
+    let enabled = |rules: Vec<RuleSpec>| -> Vec<RuleSpec> {
+        rules
+            .into_iter()
+            .filter(|r| r.status == RuleStatus::Enabled)
+            .collect()
+    };
+    let current_rules = enabled(
+        get_lifecycle_rules(client, bucket)
+            .await?
+            .iter()
+            .map(RuleSpec::try_from)
+            .collect::<Result<_>>()?,
+    );
+    let proposed_rules = match policy {
+        Some(path) => {
+            let policy = LifecyclePolicy::load(path)?;
+            policy.to_rules()?;
+            Some(enabled(policy.rules))
         }
+        None => None,
+    };
 
-        let list_output = list_req
-            .send()
-            .await
-            .context("Failed to list objects")?;
-
-        if let Some(contents) = list_output.contents {
-            for object in contents {
-                if let Some(key) = object.key {
-                    // Copy object to same location with new storage class
-                    client
-                        .copy_object()
-                        .bucket(bucket)
-                        .key(&key)
-                        .copy_source(format!("{}/{}", bucket, key))
-                        .storage_class(storage_class_enum.clone())
-                        .metadata_directive(aws_sdk_s3::types::MetadataDirective::Copy)
-                        .send()
-                        .await
-                        .context(format!("Failed to archive object: {}", key))?;
-
-                    total_objects += 1;
-                    println!("  ✓ Archived: {}", key);
-                }
-            }
+    let now = DateTime::from(SystemTime::now()).secs();
+    let all_rules: Vec<RuleSpec> = current_rules
+        .iter()
+        .chain(proposed_rules.iter().flatten())
+        .cloned()
+        .collect();
+    let objects = simulate::list_objects(bulk_client, retry, bucket, &all_rules, now).await?;
+    let total_bytes: i64 = objects.iter().map(|o| o.size).sum();
+
+    let project = |rules: &[RuleSpec]| -> Result<Projection> {
+        let predictions: Vec<_> = objects
+            .iter()
+            .map(|object| simulate::predict(rules, object))
+            .collect();
+        cost::project(&objects, &predictions, pricing, now, months)
+    };
+    let mut scenarios = vec![("Current", project(&current_rules)?)];
+    if let Some(rules) = &proposed_rules {
+        scenarios.push(("Proposed", project(rules)?));
+    }
+
+    println!(
+        "Cost projection for bucket '{}' ({}, {} months, {} objects, {})\n",
+        bucket,
+        region,
+        months,
+        objects.len(),
+        format_bytes(total_bytes)
+    );
+
+    print!("{:<8}", "MONTH");
+    for (name, _) in &scenarios {
+        print!("  {:>14}", name.to_uppercase());
+    }
+    println!();
+    for month in 0..months as usize {
+        print!("{:<8}", month + 1);
+        for (_, projection) in &scenarios {
+            print!("  {:>14}", format!("${:.2}", projection.monthly[month]));
+        }
+        println!();
+    }
+    print!("{:<8}", "TOTAL");
+    for (_, projection) in &scenarios {
+        print!("  {:>14}", format!("${:.2}", projection.total()));
+    }
+    println!();
+
+    for (name, projection) in &scenarios {
+        println!("\n{} policy:", name);
+        println!("  Storage:                     ${:.2}", projection.storage);
+
+        if projection.transitions.is_empty() {
+            println!("  Transition requests:         (none)");
+        }
+        for (class, charge) in &projection.transitions {
+            println!(
+                "  Transition requests → {:<7}{:>8} object(s)  ${:.2}",
+                format!("{}:", class),
+                charge.objects,
+                charge.cost
+            );
         }
 
-        if list_output.is_truncated == Some(true) {
-            continuation_token = list_output.next_continuation_token;
+        if projection.early_deletions.is_empty() {
+            println!("  Early-deletion penalties:    (none)");
+        }
+        for (class, charge) in &projection.early_deletions {
+            println!(
+                "  ⚠ Early deletion from {:<7}{:>8} object(s)  ${:.2}",
+                format!("{}:", class),
+                charge.objects,
+                charge.cost
+            );
+        }
+
+        println!(
+            "  Retrieving everything at the end of the period would cost ${:.2}",
+            projection.retrieval_at_end
+        );
+    }
+
+    if let [(_, current), (_, proposed)] = scenarios.as_slice() {
+        let difference = proposed.total() - current.total();
+        let verdict = if difference <= 0.0 {
+            "saves"
         } else {
-            break;
+            "costs an extra"
+        };
+        println!(
+            "\nProposed policy {} ${:.2} over {} months",
+            verdict,
+            difference.abs(),
+            months
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the bucket's lifecycle rules, or no rules if it has no lifecycle configuration.
+async fn get_lifecycle_rules(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+) -> Result<Vec<LifecycleRule>> {
+    match client
+        .get_bucket_lifecycle_configuration()
+        .bucket(bucket)
+        .send()
+        .await
+    {
+        Ok(output) => Ok(output.rules.unwrap_or_default()),
+        Err(e) if e.code() == Some("NoSuchLifecycleConfiguration") => Ok(vec![]),
+        Err(e) => Err(e).context("Failed to get lifecycle configuration"),
+    }
+}
+
+/// What `write_lifecycle_rules` did with a bucket's configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WriteOutcome {
+    Unchanged,
+    DryRun,
+    Written,
+}
+
+impl std::fmt::Display for WriteOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteOutcome::Unchanged => write!(f, "no changes"),
+            WriteOutcome::DryRun => write!(f, "changes not written (dry run)"),
+            WriteOutcome::Written => write!(f, "updated"),
         }
     }
+}
+
+/// Prints the plan from `current` to `desired` and, unless this is a dry run, writes
+/// `desired` as the bucket's lifecycle configuration.
+async fn write_lifecycle_rules(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    current: &[LifecycleRule],
+    desired: Vec<LifecycleRule>,
+    dry_run: bool,
+) -> Result<WriteOutcome> {
+    let plan = Plan::new(current, &desired)?;
+    plan.print(bucket);
+    println!();
+
+    if !check_lifecycle_rules(&desired) {
+        anyhow::bail!("Lifecycle configuration failed validation; no changes written");
+    }
 
-    println!("\n✓ Archived {} objects to {}", total_objects, storage_class);
+    if plan.is_empty() {
+        return Ok(WriteOutcome::Unchanged);
+    }
+
+    if dry_run {
+        println!("Dry run: no changes written.");
+        return Ok(WriteOutcome::DryRun);
+    }
+
+    if desired.is_empty() {
+        // Delete entire lifecycle configuration if no rules left
+        client
+            .delete_bucket_lifecycle()
+            .bucket(bucket)
+            .send()
+            .await
+            .context("Failed to delete lifecycle configuration")?;
+    } else {
+        let lifecycle_config = LifecycleConfiguration::builder()
+            .set_rules(Some(desired))
+            .build()?;
+
+        client
+            .put_bucket_lifecycle_configuration()
+            .bucket(bucket)
+            .lifecycle_configuration(lifecycle_config)
+            .send()
+            .await
+            .context("Failed to update lifecycle configuration")?;
+    }
+
+    Ok(WriteOutcome::Written)
+}
+
+fn print_rules(policy: &LifecyclePolicy, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => print!("{}", output::rules_table(&policy.rules)),
+        _ => print!("{}", format.render(policy)?),
+    }
     Ok(())
 }
 
@@ -426,6 +1572,28 @@ fn print_rule(rule: &LifecycleRule) {
                     println!("Prefix: {}", p);
                 }
             }
+            LifecycleRuleFilter::Tag(tag) => println!("Tag: {}={}", tag.key, tag.value),
+            LifecycleRuleFilter::ObjectSizeGreaterThan(size) => {
+                println!("Object Size: > {} bytes", size)
+            }
+            LifecycleRuleFilter::ObjectSizeLessThan(size) => {
+                println!("Object Size: < {} bytes", size)
+            }
+            LifecycleRuleFilter::And(and) => {
+                println!("Filter (all of):");
+                if let Some(p) = and.prefix.as_deref().filter(|p| !p.is_empty()) {
+                    println!("  Prefix: {}", p);
+                }
+                for tag in and.tags() {
+                    println!("  Tag: {}={}", tag.key, tag.value);
+                }
+                if let Some(size) = and.object_size_greater_than {
+                    println!("  Object Size: > {} bytes", size);
+                }
+                if let Some(size) = and.object_size_less_than {
+                    println!("  Object Size: < {} bytes", size);
+                }
+            }
             _ => println!("Filter: {:?}", filter),
         }
     }
@@ -435,6 +1603,8 @@ fn print_rule(rule: &LifecycleRule) {
         for t in transitions {
             if let Some(days) = t.days {
                 println!("  - After {} days → {:?}", days, t.storage_class);
+            } else if let Some(date) = &t.date {
+                println!("  - On {} → {:?}", format_date(date), t.storage_class);
             }
         }
     }
@@ -443,7 +1613,126 @@ fn print_rule(rule: &LifecycleRule) {
         if let Some(days) = expiration.days {
             println!("\nExpiration: {} days", days);
         }
+        if let Some(date) = &expiration.date {
+            println!("\nExpiration: on {}", format_date(date));
+        }
+        if expiration.expired_object_delete_marker == Some(true) {
+            println!("\nExpiration: expired object delete markers");
+        }
+    }
+
+    if let Some(transitions) = &rule.noncurrent_version_transitions {
+        println!("\nNoncurrent Version Transitions:");
+        for t in transitions {
+            if let Some(days) = t.noncurrent_days {
+                print!(
+                    "  - After {} days noncurrent → {}",
+                    days,
+                    t.storage_class
+                        .as_ref()
+                        .map(|c| c.as_str())
+                        .unwrap_or("N/A")
+                );
+                match t.newer_noncurrent_versions {
+                    Some(versions) => println!(" (keeping {} newer versions)", versions),
+                    None => println!(),
+                }
+            }
+        }
+    }
+
+    if let Some(expiration) = &rule.noncurrent_version_expiration {
+        if let Some(days) = expiration.noncurrent_days {
+            print!("\nNoncurrent Version Expiration: {} days", days);
+            match expiration.newer_noncurrent_versions {
+                Some(versions) => println!(" (keeping {} newer versions)", versions),
+                None => println!(),
+            }
+        }
+    }
+
+    if let Some(abort) = &rule.abort_incomplete_multipart_upload {
+        if let Some(days) = abort.days_after_initiation {
+            println!("\nAbort Incomplete Multipart Uploads: after {} days", days);
+        }
     }
 
     println!("{:-<80}", "");
-}
\ No newline at end of file
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_repeated_rule_tag_key() {
+        let cli = Cli::try_parse_from([
+            "s3-lifecycle",
+            "create",
+            "--bucket",
+            "b",
+            "--id",
+            "r",
+            "--glacier-days",
+            "30",
+            "--tag",
+            "class=a",
+            "--tag",
+            "class=b",
+        ])
+        .unwrap();
+        let Commands::Create { rule, .. } = cli.command else {
+            panic!("expected create");
+        };
+        assert!(rule.to_spec("r").is_err());
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("180d"), Ok(180 * 86_400));
+        assert_eq!(parse_age("2w"), Ok(14 * 86_400));
+        assert_eq!(parse_age("36h"), Ok(36 * 3_600));
+        assert_eq!(parse_age("30"), Ok(30 * 86_400));
+        assert!(parse_age("3y").is_err());
+    }
+
+    #[test]
+    fn test_parse_part_size() {
+        assert_eq!(parse_part_size("512MB"), Ok(512 << 20));
+        assert!(parse_part_size("1MB").is_err());
+        assert!(parse_part_size("6GB").is_err());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 << 30), "5.0 GB");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("128KB"), Ok(128 * 1024));
+        assert_eq!(parse_size("5GB"), Ok(5 * 1024 * 1024 * 1024));
+        assert!(parse_size("5XB").is_err());
+        assert!(parse_size("GB").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("class=logs"),
+            Ok(("class".to_string(), "logs".to_string()))
+        );
+        assert!(parse_key_value("class").is_err());
+        assert!(parse_key_value("=logs").is_err());
+    }
+}
@@ -0,0 +1,468 @@
+// Predicts what lifecycle rules will do to existing objects, without touching the bucket.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::{Context, Result};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+
+use crate::archive::{get_tags, list_page};
+use crate::policy::{parse_date, RuleSpec};
+use crate::retry::Retry;
+use crate::validate::storage_class_rank;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Tag requests in flight at once while listing objects.
+const TAG_REQUESTS: usize = 16;
+
+/// Since September 2024 S3 skips transitions for objects smaller than 128 KB unless
+/// the rule has an explicit object size filter.
+const DEFAULT_MINIMUM_TRANSITION_SIZE: i64 = 128 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimObject {
+    pub key: String,
+    pub size: i64,
+    /// Seconds since the Unix epoch
+    pub last_modified: i64,
+    pub storage_class: String,
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Transition(String),
+    Expire,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Seconds since the Unix epoch
+    pub at: i64,
+    pub rule_id: String,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prediction {
+    pub rule_ids: Vec<String>,
+    /// Lifecycle actions in the order they take effect
+    pub events: Vec<Event>,
+}
+
+impl Prediction {
+    /// Storage class the object is in at `at`, or `None` if it has expired by then.
+    pub fn storage_class_at(&self, object: &SimObject, at: i64) -> Option<String> {
+        let mut storage_class = object.storage_class.clone();
+
+        for event in self.events.iter().take_while(|e| e.at <= at) {
+            match &event.action {
+                Action::Expire => return None,
+                Action::Transition(target) => storage_class = target.clone(),
+            }
+        }
+
+        Some(storage_class)
+    }
+}
+
+/// Lists every object in the bucket page by page, fetching tags only for objects that a
+/// tag-filtered rule could match. Tag requests for each page run `TAG_REQUESTS` at a time,
+/// and every request goes through `retry` and its rate limit.
+pub async fn list_objects(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    rules: &[RuleSpec],
+    now: i64,
+) -> Result<Vec<SimObject>> {
+    let needs_tags = |key: &str| {
+        rules.iter().any(|r| {
+            !r.filter.tags.is_empty()
+                && r.filter
+                    .prefix
+                    .as_deref()
+                    .is_none_or(|prefix| key.starts_with(prefix))
+        })
+    };
+
+    let mut objects = Vec::new();
+    let mut continuation_token = None;
+
+    loop {
+        let page = list_page(client, retry, bucket, "", continuation_token.take()).await?;
+
+        // Index into `objects` of each object whose tags are being fetched
+        let mut pending = VecDeque::new();
+        for object in page.contents.unwrap_or_default() {
+            let key = object.key.unwrap_or_default();
+            if needs_tags(&key) {
+                if pending.len() >= TAG_REQUESTS {
+                    finish_tags(&mut objects, &mut pending).await?;
+                }
+                let (client, retry, bucket, key) = (
+                    client.clone(),
+                    retry.clone(),
+                    bucket.to_string(),
+                    key.clone(),
+                );
+                let handle =
+                    tokio::spawn(async move { get_tags(&client, &retry, &bucket, &key).await });
+                pending.push_back((objects.len(), handle));
+            }
+
+            objects.push(SimObject {
+                size: object.size.unwrap_or_default(),
+                last_modified: object.last_modified.map(|t| t.secs()).unwrap_or(now),
+                storage_class: object
+                    .storage_class
+                    .map(|c| c.as_str().to_string())
+                    .unwrap_or_else(|| "STANDARD".to_string()),
+                tags: BTreeMap::new(),
+                key,
+            });
+        }
+        while !pending.is_empty() {
+            finish_tags(&mut objects, &mut pending).await?;
+        }
+
+        if page.is_truncated != Some(true) {
+            break;
+        }
+        continuation_token = page.next_continuation_token;
+    }
+
+    Ok(objects)
+}
+
+type TagRequest = (
+    usize,
+    tokio::task::JoinHandle<Result<Vec<(String, String)>>>,
+);
+
+/// Waits for the oldest tag request and stores the tags on its object.
+async fn finish_tags(objects: &mut [SimObject], pending: &mut VecDeque<TagRequest>) -> Result<()> {
+    if let Some((index, handle)) = pending.pop_front() {
+        let tags = handle.await.context("Tag request panicked")??;
+        objects[index].tags = tags.into_iter().collect();
+    }
+    Ok(())
+}
+
+/// Whether an object matches a rule's filter. Tags are only compared when the rule has a
+/// tag filter, so callers may leave them empty otherwise.
+pub fn rule_matches(rule: &RuleSpec, object: &SimObject) -> bool {
+    let filter = &rule.filter;
+
+    filter
+        .prefix
+        .as_ref()
+        .is_none_or(|prefix| object.key.starts_with(prefix.as_str()))
+        && filter
+            .object_size_greater_than
+            .is_none_or(|size| object.size > size)
+        && filter
+            .object_size_less_than
+            .is_none_or(|size| object.size < size)
+        && filter
+            .tags
+            .iter()
+            .all(|(key, value)| object.tags.get(key) == Some(value))
+}
+
+/// Works out every current-version transition and expiration the given rules will apply
+/// to an object. When rules overlap, S3 favours expiration over transition and the colder
+/// class over the warmer one, and never moves an object back to a warmer class.
+pub fn predict(rules: &[RuleSpec], object: &SimObject) -> Prediction {
+    let mut prediction = Prediction::default();
+    let mut candidates = Vec::new();
+
+    for rule in rules.iter().filter(|rule| rule_matches(rule, object)) {
+        prediction.rule_ids.push(rule.id.clone());
+
+        let has_size_filter = rule.filter.object_size_greater_than.is_some()
+            || rule.filter.object_size_less_than.is_some();
+        let transitions_apply = has_size_filter || object.size >= DEFAULT_MINIMUM_TRANSITION_SIZE;
+
+        for transition in rule.transitions.iter().filter(|_| transitions_apply) {
+            if let Some(at) = action_time(object, transition.days, transition.date.as_deref()) {
+                candidates.push(Event {
+                    at,
+                    rule_id: rule.id.clone(),
+                    action: Action::Transition(transition.storage_class.clone()),
+                });
+            }
+        }
+
+        if let Some(expiration) = &rule.expiration {
+            if let Some(at) = action_time(object, expiration.days, expiration.date.as_deref()) {
+                candidates.push(Event {
+                    at,
+                    rule_id: rule.id.clone(),
+                    action: Action::Expire,
+                });
+            }
+        }
+    }
+
+    // Expirations sort ahead of transitions on the same day, colder classes ahead of warmer ones
+    candidates.sort_by_key(|event| {
+        let priority = match &event.action {
+            Action::Expire => 0,
+            Action::Transition(class) => 100 - i32::from(rank(class)),
+        };
+        (event.at, priority)
+    });
+
+    let mut current_rank = rank(&object.storage_class);
+    for event in candidates {
+        match &event.action {
+            Action::Expire => {
+                prediction.events.push(event);
+                break;
+            }
+            Action::Transition(class) if rank(class) > current_rank => {
+                current_rank = rank(class);
+                prediction.events.push(event);
+            }
+            Action::Transition(_) => {}
+        }
+    }
+
+    prediction
+}
+
+fn rank(storage_class: &str) -> u8 {
+    storage_class_rank(storage_class).unwrap_or(0)
+}
+
+/// S3 adds the rule's days to the object's creation time and rounds up to the next midnight
+/// UTC. Date-based actions apply on the date, or straight away to objects created after it.
+fn action_time(object: &SimObject, days: Option<i32>, date: Option<&str>) -> Option<i64> {
+    match (days, date) {
+        (Some(days), _) => {
+            let at = object.last_modified + i64::from(days) * SECONDS_PER_DAY;
+            Some((at + SECONDS_PER_DAY - 1).div_euclid(SECONDS_PER_DAY) * SECONDS_PER_DAY)
+        }
+        (None, Some(date)) => parse_date(date)
+            .ok()
+            .map(|date| date.secs().max(object.last_modified)),
+        (None, None) => None,
+    }
+}
+
+/// First `depth` path segments of a key, including the trailing `/`.
+pub fn key_prefix(key: &str, depth: usize) -> String {
+    let mut end = 0;
+    for _ in 0..depth {
+        match key[end..].find('/') {
+            Some(index) => end += index + 1,
+            None => break,
+        }
+    }
+    key[..end].to_string()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimelineEntry {
+    pub objects: u64,
+    pub bytes: i64,
+}
+
+/// Upcoming actions between `from` and `until`, grouped by day, key prefix and action.
+pub fn timeline(
+    objects: &[SimObject],
+    predictions: &[Prediction],
+    from: i64,
+    until: Option<i64>,
+    depth: usize,
+) -> BTreeMap<(i64, String, String), TimelineEntry> {
+    let mut timeline: BTreeMap<(i64, String, String), TimelineEntry> = BTreeMap::new();
+
+    for (object, prediction) in objects.iter().zip(predictions) {
+        let upcoming = prediction
+            .events
+            .iter()
+            .filter(|e| e.at >= from && until.is_none_or(|until| e.at <= until));
+
+        for event in upcoming {
+            let action = match &event.action {
+                Action::Transition(class) => format!("→ {}", class),
+                Action::Expire => "expire".to_string(),
+            };
+            let entry = timeline
+                .entry((event.at, key_prefix(&object.key, depth), action))
+                .or_default();
+            entry.objects += 1;
+            entry.bytes += object.size;
+        }
+    }
+
+    timeline
+}
+
+/// Number of objects and bytes in each storage class at `at`; expired objects are
+/// counted under `EXPIRED`.
+pub fn class_summary(
+    objects: &[SimObject],
+    predictions: &[Prediction],
+    at: i64,
+) -> HashMap<String, TimelineEntry> {
+    let mut summary: HashMap<String, TimelineEntry> = HashMap::new();
+
+    for (object, prediction) in objects.iter().zip(predictions) {
+        let class = prediction
+            .storage_class_at(object, at)
+            .unwrap_or_else(|| "EXPIRED".to_string());
+        let entry = summary.entry(class).or_default();
+        entry.objects += 1;
+        entry.bytes += object.size;
+    }
+
+    summary
+}
+
+/// Formats epoch seconds as `YYYY-MM-DD`.
+pub fn format_day(secs: i64) -> String {
+    DateTime::from_secs(secs)
+        .fmt(DateTimeFormat::DateTime)
+        .map(|timestamp| timestamp[..10].to_string())
+        .unwrap_or_else(|_| secs.to_string())
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::PolicyFormat;
+
+    const MB: i64 = 1024 * 1024;
+
+    fn specs(yaml: &str) -> Vec<RuleSpec> {
+        PolicyFormat::Yaml.parse(yaml).unwrap().rules
+    }
+
+    fn object(key: &str, size: i64, created: &str) -> SimObject {
+        SimObject {
+            key: key.to_string(),
+            size,
+            last_modified: parse_date(created).unwrap().secs(),
+            storage_class: "STANDARD".to_string(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    fn day(date: &str) -> i64 {
+        parse_date(date).unwrap().secs()
+    }
+
+    #[test]
+    fn test_tiered_prediction() {
+        let rules = specs(
+            r#"
+rules:
+  - id: logs
+    filter:
+      prefix: logs/
+    transitions:
+      - days: 30
+        storage_class: STANDARD_IA
+      - days: 90
+        storage_class: GLACIER
+    expiration:
+      days: 365
+"#,
+        );
+        let log = object("logs/app.log", 10 * MB, "2026-01-01T10:30:00Z");
+        let prediction = predict(&rules, &log);
+
+        assert_eq!(prediction.rule_ids, vec!["logs"]);
+        assert_eq!(prediction.events.len(), 3);
+        // 30 days after 2026-01-01 10:30 rounds up to the following midnight
+        assert_eq!(format_day(prediction.events[0].at), "2026-02-01");
+        assert_eq!(
+            prediction.storage_class_at(&log, day("2026-03-01")),
+            Some("STANDARD_IA".to_string())
+        );
+        assert_eq!(
+            prediction.storage_class_at(&log, day("2026-06-01")),
+            Some("GLACIER".to_string())
+        );
+        assert_eq!(prediction.storage_class_at(&log, day("2027-06-01")), None);
+
+        let other = object("data/file.csv", 10 * MB, "2026-01-01");
+        assert_eq!(predict(&rules, &other), Prediction::default());
+    }
+
+    #[test]
+    fn test_overlapping_rules_prefer_colder_class() {
+        let rules = specs(
+            r#"
+rules:
+  - id: ia
+    transitions:
+      - days: 60
+        storage_class: STANDARD_IA
+  - id: glacier
+    filter:
+      tags:
+        archive: "true"
+    transitions:
+      - days: 30
+        storage_class: GLACIER
+"#,
+        );
+        let mut tagged = object("a.bin", 10 * MB, "2026-01-01");
+        tagged
+            .tags
+            .insert("archive".to_string(), "true".to_string());
+
+        let prediction = predict(&rules, &tagged);
+        assert_eq!(prediction.rule_ids, vec!["ia", "glacier"]);
+        // Moving to STANDARD_IA after GLACIER would be a move to a warmer class
+        assert_eq!(prediction.events.len(), 1);
+        assert_eq!(prediction.events[0].rule_id, "glacier");
+    }
+
+    #[test]
+    fn test_small_objects_skip_transitions() {
+        let rules = specs(
+            "rules:\n  - id: r\n    transitions:\n      - days: 30\n        storage_class: GLACIER\n",
+        );
+        let small = object("tiny.txt", 1024, "2026-01-01");
+        assert!(predict(&rules, &small).events.is_empty());
+    }
+
+    #[test]
+    fn test_timeline_groups_by_prefix() {
+        let rules = specs(
+            "rules:\n  - id: r\n    transitions:\n      - days: 30\n        storage_class: GLACIER\n",
+        );
+        let objects = vec![
+            object("logs/a/1.log", MB, "2026-01-01"),
+            object("logs/b/2.log", 2 * MB, "2026-01-01"),
+            object("data/3.csv", 4 * MB, "2026-01-01"),
+        ];
+        let predictions: Vec<Prediction> = objects.iter().map(|o| predict(&rules, o)).collect();
+
+        let timeline = timeline(&objects, &predictions, day("2026-01-01"), None, 1);
+        let at = day("2026-01-31");
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(
+            timeline[&(at, "logs/".to_string(), "→ GLACIER".to_string())],
+            TimelineEntry {
+                objects: 2,
+                bytes: 3 * MB
+            }
+        );
+        assert!(timeline.contains_key(&(at, "data/".to_string(), "→ GLACIER".to_string())));
+    }
+
+    #[test]
+    fn test_key_prefix() {
+        assert_eq!(key_prefix("logs/app/1.log", 1), "logs/");
+        assert_eq!(key_prefix("logs/app/1.log", 2), "logs/app/");
+        assert_eq!(key_prefix("logs/app/1.log", 5), "logs/app/");
+        assert_eq!(key_prefix("root.txt", 1), "");
+    }
+}
@@ -0,0 +1,251 @@
+// Storage class breakdown of a bucket by key prefix and object age, the starting point
+// for deciding which lifecycle rules a bucket needs.
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use aws_sdk_s3::primitives::DateTime;
+use serde::Serialize;
+
+use crate::archive::{list_page, Totals};
+use crate::output::{self, OutputFormat};
+use crate::retry::Retry;
+use crate::simulate::key_prefix;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const HISTOGRAM_WIDTH: usize = 30;
+
+/// Age ranges for the histogram, by their upper bound in days. The bounds line up with
+/// the minimum storage durations of STANDARD_IA (30), GLACIER (90) and DEEP_ARCHIVE (180).
+const AGE_RANGES: &[(&str, Option<i64>)] = &[
+    ("< 30 days", Some(30)),
+    ("30-90 days", Some(90)),
+    ("90-180 days", Some(180)),
+    ("180-365 days", Some(365)),
+    ("1-2 years", Some(730)),
+    ("> 2 years", None),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgeRange {
+    pub label: &'static str,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketStats {
+    pub total: Totals,
+    pub by_class: BTreeMap<String, Totals>,
+    /// Storage classes under each key prefix, cut to `depth` path segments
+    pub by_prefix: BTreeMap<String, BTreeMap<String, Totals>>,
+    /// Objects by time since they were last modified
+    pub by_age: Vec<AgeRange>,
+}
+
+impl Default for BucketStats {
+    fn default() -> Self {
+        BucketStats {
+            total: Totals::default(),
+            by_class: BTreeMap::new(),
+            by_prefix: BTreeMap::new(),
+            by_age: AGE_RANGES
+                .iter()
+                .map(|(label, _)| AgeRange {
+                    label,
+                    totals: Totals::default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl BucketStats {
+    pub fn add(&mut self, prefix: String, storage_class: &str, size: i64, age_days: i64) {
+        self.total.add(size);
+        self.by_class
+            .entry(storage_class.to_string())
+            .or_default()
+            .add(size);
+        self.by_prefix
+            .entry(prefix)
+            .or_default()
+            .entry(storage_class.to_string())
+            .or_default()
+            .add(size);
+        self.by_age[age_range(age_days)].totals.add(size);
+    }
+}
+
+/// Index into `AGE_RANGES` for an object `age_days` old.
+fn age_range(age_days: i64) -> usize {
+    AGE_RANGES
+        .iter()
+        .position(|(_, limit)| limit.is_none_or(|limit| age_days < limit))
+        .unwrap_or(AGE_RANGES.len() - 1)
+}
+
+/// Lists every object under `prefix` and totals them by prefix, class and age.
+pub async fn collect(
+    client: &aws_sdk_s3::Client,
+    retry: &Retry,
+    bucket: &str,
+    prefix: &str,
+    depth: usize,
+) -> Result<BucketStats> {
+    let now = DateTime::from(SystemTime::now()).secs();
+    let mut stats = BucketStats::default();
+    let mut continuation_token = None;
+
+    loop {
+        let page = list_page(client, retry, bucket, prefix, continuation_token.take()).await?;
+
+        for object in page.contents.unwrap_or_default() {
+            let Some(key) = object.key.as_deref() else {
+                continue;
+            };
+            let storage_class = object
+                .storage_class
+                .as_ref()
+                .map_or("STANDARD", |class| class.as_str());
+            let last_modified = object.last_modified.map_or(now, |date| date.secs());
+            stats.add(
+                key_prefix(key, depth),
+                storage_class,
+                object.size.unwrap_or_default(),
+                (now - last_modified) / SECONDS_PER_DAY,
+            );
+        }
+
+        if page.is_truncated != Some(true) {
+            break;
+        }
+        continuation_token = page.next_continuation_token;
+    }
+
+    Ok(stats)
+}
+
+pub fn print_stats(
+    stats: &BucketStats,
+    bucket: &str,
+    depth: usize,
+    format: Option<OutputFormat>,
+) -> Result<()> {
+    if let Some(format) = format.filter(|format| format.is_structured()) {
+        print!("{}", format.render(stats)?);
+        return Ok(());
+    }
+
+    println!(
+        "Bucket '{}': {} object(s), {}",
+        bucket,
+        stats.total.objects,
+        crate::format_bytes(stats.total.bytes)
+    );
+
+    println!("\nBy storage class:");
+    for (class, totals) in &stats.by_class {
+        println!(
+            "  {:<19} {:>8} object(s)  {:>10}",
+            class,
+            totals.objects,
+            crate::format_bytes(totals.bytes)
+        );
+    }
+
+    println!("\nBy prefix (depth {}):", depth);
+    let rows: Vec<Vec<String>> = stats
+        .by_prefix
+        .iter()
+        .flat_map(|(prefix, classes)| {
+            let prefix = if prefix.is_empty() { "(root)" } else { prefix };
+            classes.iter().map(move |(class, totals)| {
+                vec![
+                    prefix.to_string(),
+                    class.clone(),
+                    totals.objects.to_string(),
+                    crate::format_bytes(totals.bytes),
+                ]
+            })
+        })
+        .collect();
+    print!(
+        "{}",
+        output::table(&["PREFIX", "CLASS", "OBJECTS", "SIZE"], &rows)
+    );
+
+    println!("\nBy age (since last modified):");
+    let largest = stats
+        .by_age
+        .iter()
+        .map(|range| range.totals.bytes)
+        .max()
+        .unwrap_or_default();
+    for range in &stats.by_age {
+        println!(
+            "  {:<13} {:>8} object(s)  {:>10}  {}",
+            range.label,
+            range.totals.objects,
+            crate::format_bytes(range.totals.bytes),
+            "#".repeat(bar_width(range.totals.bytes, largest))
+        );
+    }
+
+    Ok(())
+}
+
+/// Histogram bar length for `bytes`, scaled so the largest range fills the width.
+fn bar_width(bytes: i64, largest: i64) -> usize {
+    if largest <= 0 {
+        return 0;
+    }
+    let width = (bytes as f64 / largest as f64 * HISTOGRAM_WIDTH as f64).round() as usize;
+    // Keep non-empty ranges visible
+    if bytes > 0 {
+        width.max(1)
+    } else {
+        width
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age_range() {
+        assert_eq!(AGE_RANGES[age_range(0)].0, "< 30 days");
+        assert_eq!(AGE_RANGES[age_range(30)].0, "30-90 days");
+        assert_eq!(AGE_RANGES[age_range(364)].0, "180-365 days");
+        assert_eq!(AGE_RANGES[age_range(5000)].0, "> 2 years");
+    }
+
+    #[test]
+    fn test_stats_group_by_prefix_and_class() {
+        let mut stats = BucketStats::default();
+        stats.add(key_prefix("logs/2024/a.log", 2), "STANDARD", 100, 400);
+        stats.add(key_prefix("logs/2024/b.log", 2), "GLACIER", 50, 10);
+        stats.add(key_prefix("logs/2025/c.log", 2), "STANDARD", 10, 10);
+        stats.add(key_prefix("readme", 2), "STANDARD", 1, 10);
+
+        assert_eq!(stats.total.objects, 4);
+        assert_eq!(stats.total.bytes, 161);
+        assert_eq!(stats.by_class["STANDARD"].bytes, 111);
+        assert_eq!(stats.by_prefix["logs/2024/"].len(), 2);
+        assert_eq!(stats.by_prefix["logs/2025/"]["STANDARD"].objects, 1);
+        assert_eq!(stats.by_prefix[""]["STANDARD"].bytes, 1);
+        assert_eq!(stats.by_age[0].totals.objects, 3);
+        assert_eq!(stats.by_age[4].totals.bytes, 100);
+    }
+
+    #[test]
+    fn test_bar_width() {
+        assert_eq!(bar_width(0, 0), 0);
+        assert_eq!(bar_width(100, 100), HISTOGRAM_WIDTH);
+        assert_eq!(bar_width(1, 1_000_000), 1);
+        assert_eq!(bar_width(0, 100), 0);
+    }
+}
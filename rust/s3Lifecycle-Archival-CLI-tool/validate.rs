@@ -0,0 +1,548 @@
+// Offline checks of lifecycle rules against the constraints S3 enforces on
+// put_bucket_lifecycle_configuration, plus warnings for early-deletion charges.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use aws_sdk_s3::types::LifecycleRule;
+
+use crate::policy::{LifecyclePolicy, RuleSpec};
+
+pub const MAX_RULES: usize = 1000;
+pub const MAX_ID_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// S3 rejects the configuration
+    Error,
+    /// Accepted by S3, but likely to incur early-deletion charges
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub rule_id: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{} [{}]: {}", severity, self.rule_id, self.message)
+    }
+}
+
+/// Position of a storage class in the S3 transition waterfall; objects can only move
+/// to a class with a higher rank.
+pub fn storage_class_rank(storage_class: &str) -> Option<u8> {
+    match storage_class {
+        "STANDARD" => Some(0),
+        "STANDARD_IA" => Some(1),
+        "INTELLIGENT_TIERING" => Some(2),
+        "ONEZONE_IA" => Some(3),
+        "GLACIER_IR" => Some(4),
+        "GLACIER" => Some(5),
+        "DEEP_ARCHIVE" => Some(6),
+        _ => None,
+    }
+}
+
+/// Minimum number of days an object is billed for once it enters a storage class.
+pub fn minimum_storage_days(storage_class: &str) -> i32 {
+    match storage_class {
+        "STANDARD_IA" | "ONEZONE_IA" => 30,
+        "GLACIER_IR" | "GLACIER" => 90,
+        "DEEP_ARCHIVE" => 180,
+        _ => 0,
+    }
+}
+
+/// Classes S3 won't move objects out of before their minimum storage duration.
+fn is_infrequent_access(storage_class: &str) -> bool {
+    matches!(storage_class, "STANDARD_IA" | "ONEZONE_IA")
+}
+
+pub fn has_errors(violations: &[Violation]) -> bool {
+    violations.iter().any(|v| v.severity == Severity::Error)
+}
+
+/// Validates a policy file. Each rule that can't be converted to an S3 rule is reported as
+/// an error under its ID, and the rules that do convert are checked with `validate_rules`,
+/// so every problem is listed at once.
+pub fn validate_policy(policy: &LifecyclePolicy) -> Vec<Violation> {
+    let mut rules = Vec::new();
+    let mut violations = Vec::new();
+    for spec in &policy.rules {
+        match spec.build_rule() {
+            Ok(rule) => rules.push(rule),
+            Err(e) => violations.push(error(spec, format!("{:#}", e))),
+        }
+    }
+
+    violations.extend(validate_rules(&rules));
+    violations
+}
+
+pub fn validate_rules(rules: &[LifecycleRule]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if rules.len() > MAX_RULES {
+        violations.push(Violation {
+            rule_id: "*".to_string(),
+            severity: Severity::Error,
+            message: format!(
+                "configuration has {} rules; S3 allows at most {}",
+                rules.len(),
+                MAX_RULES
+            ),
+        });
+    }
+
+    let mut seen_ids = HashSet::new();
+    for rule in rules {
+        let rule = match RuleSpec::try_from(rule) {
+            Ok(rule) => rule,
+            Err(e) => {
+                violations.push(Violation {
+                    rule_id: rule.id.clone().unwrap_or_default(),
+                    severity: Severity::Error,
+                    message: format!("{:#}", e),
+                });
+                continue;
+            }
+        };
+        if !seen_ids.insert(rule.id.clone()) {
+            violations.push(error(&rule, "rule ID is not unique".to_string()));
+        }
+        validate_rule(&rule, &mut violations);
+    }
+
+    violations
+}
+
+fn validate_rule(rule: &RuleSpec, violations: &mut Vec<Violation>) {
+    if rule.id.is_empty() {
+        violations.push(error(rule, "rule ID is empty".to_string()));
+    } else if rule.id.len() > MAX_ID_LENGTH {
+        violations.push(error(
+            rule,
+            format!(
+                "rule ID is {} characters; S3 allows at most {}",
+                rule.id.len(),
+                MAX_ID_LENGTH
+            ),
+        ));
+    }
+
+    if !rule.has_actions() {
+        violations.push(error(
+            rule,
+            "rule has no transition, expiration or cleanup action".to_string(),
+        ));
+    }
+
+    let has_tag_filter = !rule.filter.tags.is_empty();
+    if has_tag_filter && rule.abort_incomplete_multipart_upload.is_some() {
+        violations.push(error(
+            rule,
+            "abort incomplete multipart upload cannot be used with a tag filter".to_string(),
+        ));
+    }
+    if has_tag_filter
+        && rule
+            .expiration
+            .as_ref()
+            .is_some_and(|e| e.expired_object_delete_marker == Some(true))
+    {
+        violations.push(error(
+            rule,
+            "expired object delete marker cleanup cannot be used with a tag filter".to_string(),
+        ));
+    }
+
+    let uses_days = rule.transitions.iter().any(|t| t.days.is_some())
+        || rule.expiration.as_ref().is_some_and(|e| e.days.is_some());
+    let uses_dates = rule.transitions.iter().any(|t| t.date.is_some())
+        || rule.expiration.as_ref().is_some_and(|e| e.date.is_some());
+    if uses_days && uses_dates {
+        violations.push(error(
+            rule,
+            "rule mixes days-based and date-based actions".to_string(),
+        ));
+    }
+
+    // Current-version transitions. Dates are YYYY-MM-DD, so they sort as strings.
+    let mut steps: Vec<Step> = rule
+        .transitions
+        .iter()
+        .map(|t| Step {
+            days: t.days,
+            date: t.date.clone(),
+            storage_class: t.storage_class.clone(),
+        })
+        .collect();
+    let expiration = rule.expiration.as_ref().and_then(|e| {
+        if e.days.is_none() && e.date.is_none() {
+            None
+        } else {
+            Some(Step {
+                days: e.days,
+                date: e.date.clone(),
+                storage_class: String::new(),
+            })
+        }
+    });
+    check_steps(rule, "", &mut steps, expiration, violations);
+
+    // Noncurrent-version transitions
+    let mut steps: Vec<Step> = rule
+        .noncurrent_version_transitions
+        .iter()
+        .map(|t| Step {
+            days: Some(t.noncurrent_days),
+            date: None,
+            storage_class: t.storage_class.clone(),
+        })
+        .collect();
+    let expiration = rule.noncurrent_version_expiration.as_ref().map(|e| Step {
+        days: Some(e.noncurrent_days),
+        date: None,
+        storage_class: String::new(),
+    });
+    check_steps(rule, "noncurrent ", &mut steps, expiration, violations);
+}
+
+struct Step {
+    days: Option<i32>,
+    date: Option<String>,
+    storage_class: String,
+}
+
+impl Step {
+    fn when(&self) -> String {
+        match (self.days, &self.date) {
+            (Some(days), _) => format!("day {}", days),
+            (None, Some(date)) => date.clone(),
+            (None, None) => "?".to_string(),
+        }
+    }
+}
+
+fn check_steps(
+    rule: &RuleSpec,
+    kind: &str,
+    steps: &mut [Step],
+    expiration: Option<Step>,
+    violations: &mut Vec<Violation>,
+) {
+    steps.sort_by(|a, b| (a.days, &a.date).cmp(&(b.days, &b.date)));
+
+    for step in steps.iter() {
+        if storage_class_rank(&step.storage_class).is_none() {
+            violations.push(error(
+                rule,
+                format!(
+                    "unknown {}transition storage class '{}'",
+                    kind, step.storage_class
+                ),
+            ));
+        }
+        if let Some(days) = step.days {
+            if days < 0 {
+                violations.push(error(
+                    rule,
+                    format!("{}transition days must not be negative", kind),
+                ));
+            }
+            // S3 only enforces the minimum for the infrequent-access classes
+            let minimum = minimum_storage_days(&step.storage_class);
+            if is_infrequent_access(&step.storage_class) && days < minimum {
+                violations.push(error(
+                    rule,
+                    format!(
+                        "{}transition to {} must be at least {} days (got {})",
+                        kind, step.storage_class, minimum, days
+                    ),
+                ));
+            }
+        }
+    }
+
+    if let Some(days) = expiration.as_ref().and_then(|e| e.days) {
+        if days <= 0 {
+            violations.push(error(
+                rule,
+                format!("{}expiration days must be a positive number", kind),
+            ));
+        }
+    }
+
+    for pair in steps.windows(2) {
+        let (previous, next) = (&pair[0], &pair[1]);
+
+        if (previous.days, &previous.date) == (next.days, &next.date) {
+            violations.push(error(
+                rule,
+                format!(
+                    "{}transitions to {} and {} happen on the same {}",
+                    kind,
+                    previous.storage_class,
+                    next.storage_class,
+                    previous.when()
+                ),
+            ));
+        }
+
+        let (previous_rank, next_rank) = (
+            storage_class_rank(&previous.storage_class),
+            storage_class_rank(&next.storage_class),
+        );
+        if let (Some(previous_rank), Some(next_rank)) = (previous_rank, next_rank) {
+            if next_rank <= previous_rank {
+                violations.push(error(
+                    rule,
+                    format!(
+                        "{}transition to {} at {} must move to a colder class than {}",
+                        kind,
+                        next.storage_class,
+                        next.when(),
+                        previous.storage_class
+                    ),
+                ));
+            }
+        }
+
+        check_minimum_duration(
+            rule,
+            kind,
+            previous,
+            next,
+            Some(&next.storage_class),
+            violations,
+        );
+    }
+
+    if let (Some(last), Some(expiration)) = (steps.last(), &expiration) {
+        let expires_first = match (last.days, expiration.days, &last.date, &expiration.date) {
+            (Some(last_days), Some(expiration_days), _, _) => expiration_days <= last_days,
+            (_, _, Some(last_date), Some(expiration_date)) => expiration_date <= last_date,
+            _ => false,
+        };
+        if expires_first {
+            violations.push(error(
+                rule,
+                format!(
+                    "{}expiration at {} must come after the last transition ({} at {})",
+                    kind,
+                    expiration.when(),
+                    last.storage_class,
+                    last.when()
+                ),
+            ));
+        } else {
+            check_minimum_duration(rule, kind, last, expiration, None, violations);
+        }
+    }
+}
+
+/// Checks how long objects stay in `previous` before `next`, a transition to
+/// `next_class` or, when that is `None`, the expiration.
+fn check_minimum_duration(
+    rule: &RuleSpec,
+    kind: &str,
+    previous: &Step,
+    next: &Step,
+    next_class: Option<&str>,
+    violations: &mut Vec<Violation>,
+) {
+    let (Some(previous_days), Some(next_days)) = (previous.days, next.days) else {
+        return;
+    };
+    let minimum = minimum_storage_days(&previous.storage_class);
+    let stored = next_days - previous_days;
+
+    if stored >= 0 && stored < minimum {
+        // S3 rejects transitions out of an infrequent-access class before its minimum;
+        // leaving the other classes early is only billed
+        let severity = if next_class.is_some() && is_infrequent_access(&previous.storage_class) {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        violations.push(Violation {
+            rule_id: rule.id.clone(),
+            severity,
+            message: format!(
+                "objects stay in {} for {} days before {}{}; minimum storage duration is {} days",
+                previous.storage_class,
+                stored,
+                kind,
+                next_class.unwrap_or("expiration"),
+                minimum
+            ),
+        });
+    }
+}
+
+fn error(rule: &RuleSpec, message: String) -> Violation {
+    Violation {
+        rule_id: rule.id.clone(),
+        severity: Severity::Error,
+        message,
+    }
+}
+
+// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::PolicyFormat;
+
+    fn violations(yaml: &str) -> Vec<Violation> {
+        let rules = PolicyFormat::Yaml.parse(yaml).unwrap().to_rules().unwrap();
+        validate_rules(&rules)
+    }
+
+    #[test]
+    fn test_policy_conversion_errors_are_violations() {
+        let yaml = r#"
+rules:
+  - id: no-actions
+  - id: mixed
+    transitions:
+      - days: 30
+        storage_class: GLACIER
+    expiration:
+      date: "2030-01-01"
+  - id: early-ia
+    transitions:
+      - days: 10
+        storage_class: STANDARD_IA
+"#;
+        let found = validate_policy(&PolicyFormat::Yaml.parse(yaml).unwrap());
+        let ids: Vec<&str> = found.iter().map(|v| v.rule_id.as_str()).collect();
+        assert_eq!(ids, ["no-actions", "mixed", "early-ia"]);
+        assert!(found.iter().all(|v| v.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_valid_tiered_rule() {
+        let yaml = r#"
+rules:
+  - id: tiered
+    transitions:
+      - days: 30
+        storage_class: STANDARD_IA
+      - days: 90
+        storage_class: GLACIER
+      - days: 180
+        storage_class: DEEP_ARCHIVE
+    expiration:
+      days: 365
+"#;
+        assert_eq!(violations(yaml), vec![]);
+    }
+
+    #[test]
+    fn test_standard_ia_minimum_days() {
+        let yaml = "rules:\n  - id: ia\n    transitions:\n      - days: 10\n        storage_class: STANDARD_IA\n";
+        let found = violations(yaml);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].rule_id, "ia");
+        assert_eq!(found[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_transition_to_warmer_class() {
+        let yaml = r#"
+rules:
+  - id: backwards
+    transitions:
+      - days: 90
+        storage_class: STANDARD_IA
+      - days: 30
+        storage_class: GLACIER
+"#;
+        let found = violations(yaml);
+        assert!(has_errors(&found));
+        assert!(found[0].message.contains("colder"));
+    }
+
+    #[test]
+    fn test_expiration_before_last_transition() {
+        let yaml = r#"
+rules:
+  - id: early
+    transitions:
+      - days: 90
+        storage_class: GLACIER
+    expiration:
+      days: 60
+"#;
+        let found = violations(yaml);
+        assert!(has_errors(&found));
+        assert!(found[0].message.contains("after the last transition"));
+    }
+
+    #[test]
+    fn test_minimum_storage_duration_warning() {
+        let yaml = r#"
+rules:
+  - id: short-glacier
+    transitions:
+      - days: 30
+        storage_class: GLACIER
+    expiration:
+      days: 60
+"#;
+        let found = violations(yaml);
+        assert!(!has_errors(&found));
+        assert_eq!(found[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_transition_out_of_infrequent_access_too_early() {
+        let yaml = r#"
+rules:
+  - id: short-ia
+    transitions:
+      - days: 30
+        storage_class: STANDARD_IA
+      - days: 45
+        storage_class: GLACIER
+"#;
+        let found = violations(yaml);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].severity, Severity::Error);
+        assert!(found[0].message.contains("15 days before GLACIER"));
+    }
+
+    #[test]
+    fn test_expiration_days_must_be_positive() {
+        let found = violations("rules:\n  - id: zero\n    expiration:\n      days: 0\n");
+        assert!(has_errors(&found));
+        assert!(found[0].message.contains("positive"));
+
+        let found = violations(
+            "rules:\n  - id: zero\n    noncurrent_version_expiration:\n      noncurrent_days: 0\n",
+        );
+        assert!(found[0].message.contains("noncurrent expiration"));
+    }
+
+    #[test]
+    fn test_duplicate_and_long_ids() {
+        let long_id = "x".repeat(MAX_ID_LENGTH + 1);
+        let yaml = format!(
+            "rules:\n  - id: a\n    expiration:\n      days: 1\n  - id: a\n    expiration:\n      days: 2\n  - id: {}\n    expiration:\n      days: 3\n",
+            long_id
+        );
+        let found = violations(&yaml);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].message.contains("not unique"));
+        assert_eq!(found[1].rule_id, long_id);
+    }
+}